@@ -0,0 +1,438 @@
+use crate::{
+    config::{de_opt_duration, de_threshold},
+    metrics,
+    monitor::{Command, CommandRequest, Query, QueryRequest},
+    pair::Pair,
+};
+use anyhow::{Context, Result};
+use hyper::{
+    body::HttpBody,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    server::{conn::AddrIncoming, Builder},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use prometheus::{Encoder, TextEncoder};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    fmt,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+type Reply = (StatusCode, Value);
+
+/// Largest request body accepted, well above any valid one.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Body of `POST /rules`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThresholdRequest {
+    #[serde(deserialize_with = "de_threshold")]
+    threshold: f64,
+    /// Only change the rules of this pair.
+    pair: Option<Pair>,
+}
+
+/// Body of `POST /mute`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MuteRequest {
+    /// How long to mute for, e.g. "30m". Unmutes when left out.
+    #[serde(default, deserialize_with = "de_opt_duration")]
+    duration: Option<Duration>,
+}
+
+// Passes requests on to the main loop, which owns the monitor.
+#[derive(Clone)]
+struct Api {
+    /// The bearer token every request must carry, when set.
+    token: Option<String>,
+    queries: mpsc::Sender<QueryRequest>,
+    commands: mpsc::Sender<CommandRequest>,
+}
+
+/// Serves the HTTP API on `addr` until the returned task is aborted.
+pub fn serve(
+    addr: SocketAddr,
+    token: Option<String>,
+    queries: mpsc::Sender<QueryRequest>,
+    commands: mpsc::Sender<CommandRequest>,
+) -> Result<JoinHandle<()>> {
+    let server = bind(addr)?;
+    let api = Api {
+        token,
+        queries,
+        commands,
+    };
+    let service = make_service_fn(move |_| {
+        let api = api.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let api = api.clone();
+                async move { Ok::<_, Infallible>(api.respond(request).await) }
+            }))
+        }
+    });
+    info!("Serving the HTTP API on http://{}", addr);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = server.serve(service).await {
+            error!("HTTP API stopped: {}", e);
+        }
+    }))
+}
+
+/// Serves only `GET /metrics` on `addr`, for Prometheus to scrape without
+/// exposing the rest of the API.
+pub fn serve_metrics(addr: SocketAddr) -> Result<JoinHandle<()>> {
+    let server = bind(addr)?;
+    let service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+            let response = match (request.method(), request.uri().path()) {
+                (&Method::GET, "/metrics") => metrics_response(),
+                (_, "/metrics") => {
+                    json_response(error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"))
+                }
+                (_, path) => json_response(error(
+                    StatusCode::NOT_FOUND,
+                    format!("No such endpoint {}", path),
+                )),
+            };
+            Ok::<_, Infallible>(response)
+        }))
+    });
+    info!("Serving metrics on http://{}/metrics", addr);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = server.serve(service).await {
+            error!("Metrics server stopped: {}", e);
+        }
+    }))
+}
+
+// Binds here rather than in the server task, so a taken port is reported.
+fn bind(addr: SocketAddr) -> Result<Builder<AddrIncoming>> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+    listener.set_nonblocking(true)?;
+    Ok(Server::from_tcp(listener)?)
+}
+
+// Prometheus scrapes its own text format rather than JSON.
+fn metrics_response() -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, TextEncoder::new().format_type())
+        .body(Body::from(metrics::render()))
+        .unwrap_or_default()
+}
+
+fn json_response((status, body): Reply) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap_or_default()
+}
+
+impl Api {
+    async fn respond(&self, request: Request<Body>) -> Response<Body> {
+        if let Some(token) = &self.token {
+            let authorized = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .is_some_and(|given| given == token);
+            if !authorized {
+                return json_response(error(StatusCode::UNAUTHORIZED, "Missing or wrong token"));
+            }
+        }
+        if request.method() == Method::GET && request.uri().path() == "/metrics" {
+            return metrics_response();
+        }
+        json_response(self.route(request).await)
+    }
+
+    async fn route(&self, request: Request<Body>) -> Reply {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/health") => {
+                let (_, health) = self.query(Query::Health).await;
+                let status = match health["status"].as_str() {
+                    Some("ok") => StatusCode::OK,
+                    _ => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                (status, health)
+            }
+            (&Method::GET, "/price") => self.query(Query::Price).await,
+            (&Method::GET, "/history") => match history_pair(&request) {
+                Ok(pair) => self.query(Query::History(pair)).await,
+                Err(e) => error(StatusCode::BAD_REQUEST, e),
+            },
+            (&Method::GET, "/rules") => self.query(Query::Rules).await,
+            (&Method::POST, "/rules") => match parse::<ThresholdRequest>(request).await {
+                Ok(ThresholdRequest { threshold, pair }) => {
+                    self.command(Command::Threshold { threshold, pair }).await
+                }
+                Err(reply) => reply,
+            },
+            (&Method::POST, "/mute") => match parse::<MuteRequest>(request).await {
+                Ok(MuteRequest { duration }) => self.command(Command::Mute(duration)).await,
+                Err(reply) => reply,
+            },
+            (_, "/health" | "/price" | "/history" | "/rules" | "/mute" | "/metrics") => {
+                error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+            }
+            (_, path) => error(StatusCode::NOT_FOUND, format!("No such endpoint {}", path)),
+        }
+    }
+
+    async fn query(&self, query: Query) -> Reply {
+        let (reply_tx, reply) = oneshot::channel();
+        if self.queries.send((query, reply_tx)).await.is_err() {
+            return stopped();
+        }
+        match reply.await {
+            Ok(answer) => (StatusCode::OK, answer),
+            Err(_) => stopped(),
+        }
+    }
+
+    async fn command(&self, command: Command) -> Reply {
+        let (reply_tx, reply) = oneshot::channel();
+        if self.commands.send((command, reply_tx)).await.is_err() {
+            return stopped();
+        }
+        match reply.await {
+            Ok(message) => (StatusCode::OK, json!({ "message": message })),
+            Err(_) => stopped(),
+        }
+    }
+}
+
+fn error(status: StatusCode, message: impl fmt::Display) -> Reply {
+    (status, json!({ "error": message.to_string() }))
+}
+
+fn stopped() -> Reply {
+    error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "The monitor is shutting down",
+    )
+}
+
+// The `pair` parameter of `/history`, e.g. `?pair=BTC-USD`.
+fn history_pair(request: &Request<Body>) -> Result<Option<Pair>> {
+    let url = Url::parse(&format!("http://localhost{}", request.uri()))?;
+    url.query_pairs()
+        .find(|(name, _)| name == "pair")
+        .map(|(_, pair)| pair.parse())
+        .transpose()
+}
+
+// Reads a JSON body, giving up on ones over `MAX_BODY` without reading the
+// rest.
+async fn parse<T: DeserializeOwned>(request: Request<Body>) -> std::result::Result<T, Reply> {
+    let too_large = || {
+        error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body is over {} bytes", MAX_BODY),
+        )
+    };
+    // Browsers only send JSON cross-origin after a CORS preflight, which
+    // this API never answers, so a web page can't change the rules.
+    let json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(';').next() == Some("application/json"));
+    if !json {
+        return Err(error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Request body must be application/json",
+        ));
+    }
+    let mut body = request.into_body();
+    if body.size_hint().lower() > MAX_BODY as u64 {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| error(StatusCode::BAD_REQUEST, e))?;
+        if bytes.len() + chunk.len() > MAX_BODY {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    serde_json::from_slice(&bytes)
+        .context("Invalid request body")
+        .map_err(|e| error(StatusCode::BAD_REQUEST, format!("{:#}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body;
+
+    fn request(body: impl Into<Body>) -> Request<Body> {
+        Request::post("/rules")
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.into())
+            .unwrap()
+    }
+
+    // An API whose main loop answers every query with `answer` and every
+    // command with "done".
+    fn api(token: Option<&str>, answer: Value) -> Api {
+        let (queries, mut query_rx) = mpsc::channel::<QueryRequest>(1);
+        let (commands, mut command_rx) = mpsc::channel::<CommandRequest>(1);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some((_, reply)) = query_rx.recv() => {
+                        let _ = reply.send(answer.clone());
+                    }
+                    Some((_, reply)) = command_rx.recv() => {
+                        let _ = reply.send("done".to_string());
+                    }
+                    else => break,
+                }
+            }
+        });
+        Api {
+            token: token.map(str::to_string),
+            queries,
+            commands,
+        }
+    }
+
+    async fn respond(api: &Api, request: Request<Body>) -> (StatusCode, Value) {
+        let response = api.respond(request).await;
+        let status = response.status();
+        let body = body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn get(path: &str) -> Request<Body> {
+        Request::get(path).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn thresholds_read_like_the_config_file() {
+        for (body, threshold) in [
+            (r#"{"threshold": 0.015}"#, 0.015),
+            (r#"{"threshold": "1.5%"}"#, 0.015),
+            (r#"{"threshold": 0.5}"#, 0.5),
+        ] {
+            let parsed = parse::<ThresholdRequest>(request(body)).await.unwrap();
+            assert!((parsed.threshold - threshold).abs() < 1e-12, "{}", body);
+        }
+        let (status, _) = parse::<ThresholdRequest>(request(r#"{"threshold": 1.5}"#))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_bodies() {
+        let padded = format!(r#"{{"threshold": 0.01{}}}"#, " ".repeat(MAX_BODY));
+        let (status, _) = parse::<ThresholdRequest>(request(padded.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Without a length up front, the limit applies as chunks arrive.
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            for chunk in padded.into_bytes().chunks(1024) {
+                if sender.send_data(chunk.to_vec().into()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let (status, _) = parse::<ThresholdRequest>(request(body)).await.unwrap_err();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_that_are_not_json() {
+        let api = api(None, Value::Null);
+        let plain = Request::post("/mute")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from(r#"{"duration": "3d"}"#))
+            .unwrap();
+        assert_eq!(
+            respond(&api, plain).await.0,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let json = Request::post("/mute")
+            .header(CONTENT_TYPE, "application/json; charset=utf-8")
+            .body(Body::from(r#"{"duration": "3d"}"#))
+            .unwrap();
+        assert_eq!(
+            respond(&api, json).await,
+            (StatusCode::OK, json!({ "message": "done" }))
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_the_token_when_set() {
+        let api = api(Some("secret"), json!({}));
+        assert_eq!(
+            respond(&api, get("/price")).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        let wrong = Request::get("/price")
+            .header(AUTHORIZATION, "Bearer guess")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(respond(&api, wrong).await.0, StatusCode::UNAUTHORIZED);
+        let right = Request::get("/price")
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(respond(&api, right).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn routes_answer_with_the_right_status() {
+        let stale = api(None, json!({ "status": "stale" }));
+        assert_eq!(
+            respond(&stale, get("/health")).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({ "status": "stale" })
+            )
+        );
+        let ok = api(None, json!({ "status": "ok" }));
+        assert_eq!(respond(&ok, get("/health")).await.0, StatusCode::OK);
+
+        assert_eq!(
+            respond(&ok, get("/mute")).await.0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            respond(&ok, get("/nothing")).await,
+            (
+                StatusCode::NOT_FOUND,
+                json!({ "error": "No such endpoint /nothing" })
+            )
+        );
+        assert_eq!(
+            respond(&ok, get("/history?pair=/USD")).await.0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            respond(&ok, get("/history?pair=BTC-USD")).await.0,
+            StatusCode::OK
+        );
+    }
+}
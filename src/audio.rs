@@ -0,0 +1,208 @@
+use crate::{
+    notifier::{Alert, Notifier},
+    rule::Move,
+    sound::Sound,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{debug, error, info};
+use rodio::{
+    cpal::{self, traits::HostTrait},
+    DeviceTrait, OutputStream, OutputStreamHandle, Sink,
+};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// How many alerts the null backend remembers.
+const RECORDED: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioBackend {
+    /// Play through a sound card.
+    #[default]
+    Output,
+    /// Play nothing, for headless servers.
+    Null,
+}
+
+impl FromStr for AudioBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "output" => Ok(AudioBackend::Output),
+            "null" | "none" => Ok(AudioBackend::Null),
+            _ => anyhow::bail!("Unknown audio backend {:?}, expected output or null", s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AudioBackend {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Plays alert sounds without blocking the monitor.
+///
+/// The output backend plays on a dedicated thread, one sound after another
+/// and to completion. Alerts that arrive while a sound is playing are queued,
+/// with repeats of the same sound for moves in the same direction coalesced
+/// into one. The null backend only logs and records what would have played.
+#[derive(Clone)]
+pub enum Player {
+    Output(Sender<(Sound, Move)>),
+    Null(Arc<Mutex<VecDeque<(Sound, Move)>>>),
+}
+
+impl Player {
+    /// Starts `backend`, on the output device named `device` or the default
+    /// one, at `volume` (a fraction of full volume).
+    pub fn new(backend: AudioBackend, device: Option<String>, volume: f32) -> Result<Self> {
+        if backend == AudioBackend::Null {
+            return Ok(Player::Null(Arc::default()));
+        }
+
+        if let Some(name) = &device {
+            let names = output_devices()?;
+            if names.is_empty() {
+                anyhow::bail!("No audio output devices found for {:?}", name);
+            }
+            if !names.contains(name) {
+                anyhow::bail!(
+                    "No audio output device named {:?}, available: {}",
+                    name,
+                    names.join(", ")
+                );
+            }
+        }
+
+        let (sounds, queue) = mpsc::channel();
+        thread::Builder::new()
+            .name("audio".to_string())
+            .spawn(move || run(queue, device, volume))?;
+        Ok(Player::Output(sounds))
+    }
+
+    pub fn play(&self, sound: &Sound, change: Move) {
+        match self {
+            Player::Output(sounds) => {
+                if sounds.send((sound.clone(), change)).is_err() {
+                    error!("Audio thread has stopped, dropping {} alert", sound);
+                }
+            }
+            Player::Null(played) => {
+                info!("Would play {} for {}", sound, change);
+                let mut played = played.lock().unwrap();
+                if played.len() == RECORDED {
+                    played.pop_front();
+                }
+                played.push_back((sound.clone(), change));
+            }
+        }
+    }
+
+    /// The most recent alerts the null backend would have played.
+    #[cfg(test)]
+    pub fn played(&self) -> Vec<(Sound, Move)> {
+        match self {
+            Player::Output(_) => Vec::new(),
+            Player::Null(played) => played.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Player {
+    fn name(&self) -> &str {
+        "sound"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        self.play(&alert.sound, alert.change);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Player::Output(_) => f.write_str("Player::Output"),
+            Player::Null(_) => f.write_str("Player::Null"),
+        }
+    }
+}
+
+fn output_devices() -> Result<Vec<String>> {
+    Ok(cpal::default_host()
+        .output_devices()?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+fn open(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle)> {
+    let Some(name) = device else {
+        return Ok(OutputStream::try_default()?);
+    };
+    let device = cpal::default_host()
+        .output_devices()?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| anyhow::anyhow!("Audio output device {:?} is gone", name))?;
+    Ok(OutputStream::try_from_device(&device)?)
+}
+
+fn run(queue: Receiver<(Sound, Move)>, device: Option<String>, volume: f32) {
+    // Opened on first use and kept for the life of the thread; rodio stops
+    // playback as soon as the stream is dropped.
+    let mut output: Option<(OutputStream, OutputStreamHandle)> = None;
+
+    while let Ok(alert) = queue.recv() {
+        let mut pending = vec![alert];
+        pending.extend(queue.try_iter());
+        let received = pending.len();
+        pending.dedup_by(|(sound, change), (other, kept)| {
+            sound == other && change.direction == kept.direction
+        });
+        if pending.len() < received {
+            debug!("Coalesced {} queued alerts", received - pending.len());
+        }
+
+        for (sound, change) in pending {
+            if output.is_none() {
+                match open(device.as_deref()) {
+                    Ok(stream) => output = Some(stream),
+                    Err(e) => {
+                        error!("Failed to open audio output: {}", e);
+                        continue;
+                    }
+                }
+            }
+            if let Some((_, handle)) = &output {
+                if let Err(e) = play(handle, &sound, &change, volume) {
+                    error!("Failed to play alert sound {}: {}", sound, e);
+                }
+            }
+        }
+    }
+}
+
+fn play(handle: &OutputStreamHandle, sound: &Sound, change: &Move, volume: f32) -> Result<()> {
+    let sink = Sink::try_new(handle)?;
+    sink.set_volume(volume);
+    for source in sound.sources(change)? {
+        sink.append(source);
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
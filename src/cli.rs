@@ -0,0 +1,238 @@
+use crate::{
+    audio::AudioBackend,
+    config::PairConfig,
+    source::{AggregateMethod, SourceKind},
+};
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use log::LevelFilter;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "A subtle Bitcoin price alert")]
+pub struct Args {
+    /// Config file to use instead of searching for subtle-alert.toml in the
+    /// working directory and the XDG config directory.
+    #[arg(short, long, env = "SUBTLE_ALERT_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Pair to watch as PAIR[:THRESHOLD[:SOUND]], e.g. BTC/EUR:1%:eur.mp3.
+    /// Repeat or separate with commas to watch several pairs [default: BTC/USD]
+    #[arg(short, long = "pair", env = "SUBTLE_ALERT_PAIR", value_delimiter = ',')]
+    pub pairs: Vec<PairConfig>,
+
+    /// Price change that triggers an alert, as a percentage (0.5%) or a
+    /// fraction (0.005) [default: 0.5%]
+    #[arg(short, long, value_parser = parse_threshold)]
+    pub threshold: Option<f64>,
+
+    /// Minimum time between two alerts in the same direction for a pair,
+    /// e.g. 30s or 5m [default: 1m]
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub cooldown: Option<Duration>,
+
+    /// Alert during the cooldown anyway once the price has moved this much
+    /// further in the same direction, e.g. 1%
+    #[arg(long, value_parser = parse_threshold)]
+    pub escalate: Option<f64>,
+
+    /// Time between polls, e.g. 5s or 1m [default: 5s]
+    #[arg(short, long, value_parser = parse_interval)]
+    pub interval: Option<Duration>,
+
+    /// Sound file (mp3, wav, ogg or flac) played for pairs without their own,
+    /// or `tone` for beeps that follow the size and direction of the move
+    /// [default: built-in]
+    #[arg(long)]
+    pub sound: Option<PathBuf>,
+
+    /// Sound played when the price rises, instead of --sound
+    #[arg(long)]
+    pub sound_up: Option<PathBuf>,
+
+    /// Sound played when the price falls, instead of --sound
+    #[arg(long)]
+    pub sound_down: Option<PathBuf>,
+
+    /// Where alert sounds go: output, or null to play nothing on a headless
+    /// machine [default: output]
+    #[arg(long)]
+    pub audio: Option<AudioBackend>,
+
+    /// Name of the audio output device to play on [default: the system default]
+    #[arg(long, env = "SUBTLE_ALERT_AUDIO_DEVICE")]
+    pub audio_device: Option<String>,
+
+    /// Playback volume, as a percentage (50%) or a fraction (0.5) [default: 100%]
+    #[arg(long, value_parser = parse_volume)]
+    pub volume: Option<f32>,
+
+    /// Price sources to poll. Several sources are aggregated into one price
+    /// [default: kraken]
+    #[arg(
+        short,
+        long = "source",
+        env = "SUBTLE_ALERT_SOURCE",
+        value_delimiter = ','
+    )]
+    pub sources: Vec<SourceKind>,
+
+    /// How quotes from several sources are combined: median or vwap
+    /// [default: median]
+    #[arg(long, env = "SUBTLE_ALERT_AGGREGATE")]
+    pub aggregate: Option<AggregateMethod>,
+
+    /// Quotes further than this from the median of all sources are ignored
+    /// [default: 1%]
+    #[arg(long, value_parser = parse_threshold)]
+    pub max_deviation: Option<f64>,
+
+    /// Stream trades from Kraken's WebSocket API instead of polling.
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Kraken WebSocket endpoint used with --stream [default: wss://ws.kraken.com/v2]
+    #[arg(long, env = "SUBTLE_ALERT_KRAKEN_WS_URL")]
+    pub ws_url: Option<String>,
+
+    /// Write price ticks, alerts, fetch errors and source switches to stdout as
+    /// JSON lines.
+    #[arg(long)]
+    pub events: bool,
+
+    /// Serve an HTTP API for reading prices and changing rules on this
+    /// address, e.g. 127.0.0.1:8080
+    #[arg(long, env = "SUBTLE_ALERT_API")]
+    pub api: Option<SocketAddr>,
+
+    /// Require this bearer token on every HTTP API request
+    #[arg(long, env = "SUBTLE_ALERT_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    /// Serve only Prometheus metrics, at /metrics, on this address, e.g.
+    /// 0.0.0.0:9100
+    #[arg(long, env = "SUBTLE_ALERT_METRICS")]
+    pub metrics: Option<SocketAddr>,
+
+    /// Log verbosity. RUST_LOG, when set, takes precedence.
+    #[arg(long, value_enum, default_value = "info")]
+    pub log_level: LogLevel,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Parses `0.5%` as 0.005 and a bare number as a fraction.
+pub fn parse_threshold(s: &str) -> Result<f64> {
+    let s = s.trim();
+    let fraction = match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>()? / 100.0,
+        None => s.parse::<f64>()?,
+    };
+
+    if !(fraction > 0.0 && fraction < 1.0) {
+        anyhow::bail!("{} is not between 0% and 100%", s);
+    }
+    Ok(fraction)
+}
+
+/// Parses a volume from 0% to 100%, written like a threshold.
+pub fn parse_volume(s: &str) -> Result<f32> {
+    let s = s.trim();
+    let volume = match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>()? / 100.0,
+        None => s.parse::<f32>()?,
+    };
+
+    if !(0.0..=1.0).contains(&volume) {
+        anyhow::bail!("volume {} is not between 0% and 100%", s);
+    }
+    Ok(volume)
+}
+
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let interval = humantime::parse_duration(s)?;
+    if interval < Duration::from_secs(1) {
+        anyhow::bail!("interval must be at least 1s");
+    }
+    Ok(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair::Pair;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        Args::try_parse_from(["subtle-alert"].iter().chain(args))
+    }
+
+    #[test]
+    fn definition_is_valid() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn reads_thresholds_as_percentages_or_fractions() {
+        assert_eq!(
+            parse(&["--threshold", "0.5%"]).unwrap().threshold,
+            Some(0.005)
+        );
+        assert_eq!(
+            parse(&["--threshold", "0.005"]).unwrap().threshold,
+            Some(0.005)
+        );
+        assert!(parse(&["--threshold", "2"]).is_err());
+        assert!(parse(&["--threshold", "0%"]).is_err());
+    }
+
+    #[test]
+    fn rejects_intervals_under_a_second() {
+        assert!(parse(&["--interval", "500ms"]).is_err());
+        assert_eq!(
+            parse(&["--interval", "1m"]).unwrap().interval,
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn reads_pairs_with_threshold_and_sound() {
+        let args = parse(&["--pair", "BTC/EUR:1%:eur.mp3,BTC/JPY"]).unwrap();
+        let pairs: Vec<_> = args
+            .pairs
+            .iter()
+            .map(|p| (p.pair.clone(), p.threshold, p.sound.clone()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (
+                    Pair::new("BTC", "EUR"),
+                    Some(0.01),
+                    Some(PathBuf::from("eur.mp3"))
+                ),
+                (Pair::new("BTC", "JPY"), None, None),
+            ]
+        );
+        assert!(parse(&["--pair", "BTC/EUR:lots"]).is_err());
+    }
+}
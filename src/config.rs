@@ -0,0 +1,867 @@
+use crate::{
+    audio::{AudioBackend, Player},
+    cli::{parse_interval, parse_threshold, parse_volume, Args},
+    monitor::{CommandRequest, Watch},
+    notifier::{Notifier, NotifierConfig},
+    pair::Pair,
+    rule::{Cooldown, Direction, Levels, Rule, RuleKind, WindowReference},
+    sound::{Sound, TONE},
+    source::{AggregateMethod, Aggregator, Metered, PriceSource, SourceKind, KRAKEN_WS_URL},
+};
+use anyhow::{Context, Result};
+use log::{error, info};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use reqwest::Client;
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::mpsc, time};
+
+pub const FILE_NAME: &str = "subtle-alert.toml";
+
+const DEFAULT_HYSTERESIS: f64 = 0.001;
+
+// Editors often write a file in several steps, wait for them to settle.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Settings read from `subtle-alert.toml`, with command-line flags applied on
+/// top.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(deserialize_with = "de_interval")]
+    pub interval: Duration,
+    #[serde(deserialize_with = "de_threshold")]
+    pub threshold: f64,
+    pub sound: Option<PathBuf>,
+    pub sound_up: Option<PathBuf>,
+    pub sound_down: Option<PathBuf>,
+    #[serde(deserialize_with = "de_duration")]
+    pub cooldown: Duration,
+    #[serde(deserialize_with = "de_opt_threshold")]
+    pub escalate: Option<f64>,
+    pub stream: bool,
+    pub ws_url: String,
+    pub events: bool,
+    /// Address the HTTP API listens on, off when unset.
+    pub api: Option<SocketAddr>,
+    /// Bearer token the HTTP API requires, open to anyone when unset.
+    pub api_token: Option<String>,
+    /// Address serving only Prometheus metrics, off when unset.
+    pub metrics: Option<SocketAddr>,
+    pub sources: SourcesConfig,
+    pub audio: AudioConfig,
+    pub notifiers: Vec<NotifierConfig>,
+    pub pairs: Vec<PairConfig>,
+    // Decoded sound files by path, filled by `load_sounds`.
+    #[serde(skip)]
+    sounds: HashMap<PathBuf, Sound>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcesConfig {
+    #[serde(rename = "use")]
+    pub kinds: Vec<SourceKind>,
+    pub aggregate: AggregateMethod,
+    #[serde(deserialize_with = "de_threshold")]
+    pub max_deviation: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub backend: AudioBackend,
+    pub device: Option<String>,
+    #[serde(deserialize_with = "de_volume")]
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairConfig {
+    pub pair: Pair,
+    #[serde(default, deserialize_with = "de_opt_threshold")]
+    pub threshold: Option<f64>,
+    pub sound: Option<PathBuf>,
+    pub sound_up: Option<PathBuf>,
+    pub sound_down: Option<PathBuf>,
+    #[serde(default, deserialize_with = "de_opt_duration")]
+    pub cooldown: Option<Duration>,
+    #[serde(default, deserialize_with = "de_opt_threshold")]
+    pub escalate: Option<f64>,
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+}
+
+/// A `[[pairs.rules]]` entry. The kind of rule follows from which fields are
+/// set: `change`, or `move` with `within`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawRuleConfig")]
+pub struct RuleConfig {
+    pub id: Option<String>,
+    pub kind: RuleKind,
+    pub sound: Option<PathBuf>,
+    pub sound_up: Option<PathBuf>,
+    pub sound_down: Option<PathBuf>,
+    pub cooldown: Option<Duration>,
+    pub escalate: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRuleConfig {
+    id: Option<String>,
+    #[serde(default, deserialize_with = "de_opt_threshold")]
+    change: Option<f64>,
+    #[serde(default, rename = "move", deserialize_with = "de_opt_threshold")]
+    movement: Option<f64>,
+    #[serde(default, deserialize_with = "de_opt_duration")]
+    within: Option<Duration>,
+    from: Option<WindowReference>,
+    above: Option<f64>,
+    below: Option<f64>,
+    cross: Option<f64>,
+    every: Option<f64>,
+    direction: Option<Direction>,
+    #[serde(default, deserialize_with = "de_opt_threshold")]
+    hysteresis: Option<f64>,
+    sound: Option<PathBuf>,
+    sound_up: Option<PathBuf>,
+    sound_down: Option<PathBuf>,
+    #[serde(default, deserialize_with = "de_opt_duration")]
+    cooldown: Option<Duration>,
+    #[serde(default, deserialize_with = "de_opt_threshold")]
+    escalate: Option<f64>,
+}
+
+impl TryFrom<RawRuleConfig> for RuleConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawRuleConfig) -> Result<Self> {
+        let levels = [
+            raw.above
+                .map(|level| (Levels::Fixed(level), Some(Direction::Up))),
+            raw.below
+                .map(|level| (Levels::Fixed(level), Some(Direction::Down))),
+            raw.cross.map(|level| (Levels::Fixed(level), raw.direction)),
+            raw.every.map(|step| (Levels::Every(step), raw.direction)),
+        ];
+        let is_window = raw.movement.is_some() || raw.within.is_some() || raw.from.is_some();
+        let kinds =
+            levels.iter().flatten().count() + raw.change.iter().count() + is_window as usize;
+        if kinds != 1 {
+            anyhow::bail!(
+                "a rule needs exactly one of `change`, `move` and `within`, `above`, `below`, `cross` or `every`"
+            );
+        }
+
+        let kind = if let Some(threshold) = raw.change {
+            RuleKind::Change { threshold }
+        } else if is_window {
+            let (Some(threshold), Some(window)) = (raw.movement, raw.within) else {
+                anyhow::bail!("a window rule needs both `move` and `within`");
+            };
+            RuleKind::Window {
+                threshold,
+                window,
+                from: raw.from.unwrap_or_default(),
+            }
+        } else {
+            let (levels, direction) = levels.into_iter().flatten().next().unwrap();
+            let (Levels::Fixed(value) | Levels::Every(value)) = levels;
+            if value.is_nan() || value <= 0.0 {
+                anyhow::bail!("price levels must be positive");
+            }
+            if raw.direction.is_some() && (raw.above.is_some() || raw.below.is_some()) {
+                anyhow::bail!("`direction` only applies to `cross` and `every` rules");
+            }
+            RuleKind::Level {
+                levels,
+                direction,
+                hysteresis: raw.hysteresis.unwrap_or(DEFAULT_HYSTERESIS),
+            }
+        };
+        if (raw.hysteresis.is_some() || raw.direction.is_some())
+            && !matches!(kind, RuleKind::Level { .. })
+        {
+            anyhow::bail!("`hysteresis` and `direction` only apply to level rules");
+        }
+
+        Ok(RuleConfig {
+            id: raw.id,
+            kind,
+            sound: raw.sound,
+            sound_up: raw.sound_up,
+            sound_down: raw.sound_down,
+            cooldown: raw.cooldown,
+            escalate: raw.escalate,
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            threshold: 0.005,
+            sound: None,
+            sound_up: None,
+            sound_down: None,
+            cooldown: Duration::from_secs(60),
+            escalate: None,
+            stream: false,
+            ws_url: KRAKEN_WS_URL.to_string(),
+            events: false,
+            api: None,
+            api_token: None,
+            metrics: None,
+            sources: SourcesConfig::default(),
+            audio: AudioConfig::default(),
+            notifiers: Vec::new(),
+            pairs: vec![PairConfig::new(Pair::new("BTC", "USD"))],
+            sounds: HashMap::new(),
+        }
+    }
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self {
+            kinds: vec![SourceKind::Kraken],
+            aggregate: AggregateMethod::Median,
+            max_deviation: 0.01,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            backend: AudioBackend::Output,
+            device: None,
+            volume: 1.0,
+        }
+    }
+}
+
+impl PairConfig {
+    pub fn new(pair: Pair) -> Self {
+        Self {
+            pair,
+            threshold: None,
+            sound: None,
+            sound_up: None,
+            sound_down: None,
+            cooldown: None,
+            escalate: None,
+            rules: Vec::new(),
+        }
+    }
+
+    /// The configured rules, plus the plain change rule when the pair sets a
+    /// threshold or has no rules of its own.
+    fn rules(&self, config: &Config) -> Vec<Rule> {
+        let implicit = (self.rules.is_empty() || self.threshold.is_some()).then(|| RuleConfig {
+            id: None,
+            kind: RuleKind::Change {
+                threshold: self.threshold.unwrap_or(config.threshold),
+            },
+            sound: None,
+            sound_up: None,
+            sound_down: None,
+            cooldown: None,
+            escalate: None,
+        });
+
+        implicit
+            .iter()
+            .chain(&self.rules)
+            .map(|rule| {
+                Rule::new(
+                    rule.id.clone().unwrap_or_else(|| rule.kind.to_string()),
+                    rule.kind.clone(),
+                    config.sound(self.sound(rule, config, Direction::Up)),
+                    config.sound(self.sound(rule, config, Direction::Down)),
+                    Cooldown::new(
+                        rule.cooldown
+                            .or(self.cooldown)
+                            .unwrap_or_else(|| rule.kind.default_cooldown(config.cooldown)),
+                        rule.escalate.or(self.escalate).or(config.escalate),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    // The most specific sound for `direction`: rule, then pair, then global,
+    // with a directional sound winning over a plain one at each level. `None`
+    // is the built-in sound.
+    fn sound(&self, rule: &RuleConfig, config: &Config, direction: Direction) -> Option<PathBuf> {
+        let directional = |up: &Option<PathBuf>, down: &Option<PathBuf>| match direction {
+            Direction::Up => up.clone(),
+            Direction::Down => down.clone(),
+        };
+        directional(&rule.sound_up, &rule.sound_down)
+            .or_else(|| rule.sound.clone())
+            .or_else(|| directional(&self.sound_up, &self.sound_down))
+            .or_else(|| self.sound.clone())
+            .or_else(|| directional(&config.sound_up, &config.sound_down))
+            .or_else(|| config.sound.clone())
+    }
+}
+
+// `--pair` syntax: PAIR[:THRESHOLD[:SOUND]], unset fields fall back to the
+// global threshold and sound.
+impl FromStr for PairConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().splitn(3, ':');
+        let pair = parts.next().unwrap_or_default().parse()?;
+        let threshold = parts
+            .next()
+            .filter(|t| !t.is_empty())
+            .map(parse_threshold)
+            .transpose()
+            .with_context(|| format!("Invalid threshold for {}", pair))?;
+        let sound = parts.next().map(PathBuf::from);
+
+        Ok(PairConfig {
+            threshold,
+            sound,
+            ..PairConfig::new(pair)
+        })
+    }
+}
+
+impl Config {
+    /// Loads the config file named by `--config` or found by [`Config::find`]
+    /// and applies the command-line flags to it.
+    pub fn load(args: &Args) -> Result<(Self, Option<PathBuf>)> {
+        let path = args.config.clone().or_else(Config::find);
+        let mut config = match &path {
+            Some(path) => Config::read(path)?,
+            None => Config::default(),
+        };
+        config.apply_args(args);
+        config.validate()?;
+        config.load_sounds()?;
+        Ok((config, path))
+    }
+
+    /// Looks for `subtle-alert.toml` in the working directory, then in the
+    /// XDG config directory.
+    pub fn find() -> Option<PathBuf> {
+        let cwd = PathBuf::from(FILE_NAME);
+        let xdg = dirs::config_dir().map(|dir| dir.join("subtle-alert").join(FILE_NAME));
+        std::iter::once(cwd).chain(xdg).find(|path| path.is_file())
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+
+    fn apply_args(&mut self, args: &Args) {
+        if let Some(interval) = args.interval {
+            self.interval = interval;
+        }
+        if let Some(threshold) = args.threshold {
+            self.threshold = threshold;
+        }
+        if let Some(sound) = &args.sound {
+            self.sound = Some(sound.clone());
+        }
+        if let Some(sound) = &args.sound_up {
+            self.sound_up = Some(sound.clone());
+        }
+        if let Some(sound) = &args.sound_down {
+            self.sound_down = Some(sound.clone());
+        }
+        if let Some(cooldown) = args.cooldown {
+            self.cooldown = cooldown;
+        }
+        if let Some(escalate) = args.escalate {
+            self.escalate = Some(escalate);
+        }
+        if args.stream {
+            self.stream = true;
+        }
+        if let Some(ws_url) = &args.ws_url {
+            self.ws_url = ws_url.clone();
+        }
+        if args.events {
+            self.events = true;
+        }
+        if let Some(api) = args.api {
+            self.api = Some(api);
+        }
+        if let Some(token) = &args.api_token {
+            self.api_token = Some(token.clone());
+        }
+        if let Some(metrics) = args.metrics {
+            self.metrics = Some(metrics);
+        }
+        if !args.sources.is_empty() {
+            self.sources.kinds = args.sources.clone();
+        }
+        if let Some(aggregate) = args.aggregate {
+            self.sources.aggregate = aggregate;
+        }
+        if let Some(max_deviation) = args.max_deviation {
+            self.sources.max_deviation = max_deviation;
+        }
+        if let Some(backend) = args.audio {
+            self.audio.backend = backend;
+        }
+        if let Some(device) = &args.audio_device {
+            self.audio.device = Some(device.clone());
+        }
+        if let Some(volume) = args.volume {
+            self.audio.volume = volume;
+        }
+        if !args.pairs.is_empty() {
+            self.pairs = args.pairs.clone();
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.pairs.is_empty() {
+            anyhow::bail!("No pairs to watch");
+        }
+        if self.sources.kinds.is_empty() {
+            anyhow::bail!("No price sources configured");
+        }
+        if self.interval < Duration::from_secs(1) {
+            anyhow::bail!("interval must be at least 1s");
+        }
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if self.pairs[..i].iter().any(|other| other.pair == pair.pair) {
+                anyhow::bail!("{} is listed more than once", pair.pair);
+            }
+            let rules = pair.rules(self);
+            for (j, rule) in rules.iter().enumerate() {
+                if rules[..j].iter().any(|other| other.id == rule.id) {
+                    anyhow::bail!("Rule id {:?} is used more than once", rule.id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes every configured sound file up front.
+    fn load_sounds(&mut self) -> Result<()> {
+        let directional =
+            |sound: &Option<PathBuf>, up: &Option<PathBuf>, down: &Option<PathBuf>| {
+                [sound.clone(), up.clone(), down.clone()]
+            };
+        let paths = self
+            .pairs
+            .iter()
+            .flat_map(|pair| {
+                pair.rules
+                    .iter()
+                    .flat_map(|rule| directional(&rule.sound, &rule.sound_up, &rule.sound_down))
+                    .chain(directional(&pair.sound, &pair.sound_up, &pair.sound_down))
+            })
+            .chain(directional(&self.sound, &self.sound_up, &self.sound_down))
+            .flatten()
+            .filter(|path| path != Path::new(TONE))
+            .collect::<Vec<_>>();
+
+        let mut sounds = HashMap::new();
+        for path in paths {
+            if let Entry::Vacant(entry) = sounds.entry(path) {
+                let sound = Sound::load(entry.key())?;
+                entry.insert(sound);
+            }
+        }
+        self.sounds = sounds;
+        Ok(())
+    }
+
+    fn sound(&self, path: Option<PathBuf>) -> Sound {
+        match path {
+            Some(path) if path == Path::new(TONE) => Sound::Tone,
+            path => path
+                .and_then(|path| self.sounds.get(&path).cloned())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn source(&self, client: &Client) -> Box<dyn PriceSource> {
+        let mut sources: Vec<_> = self
+            .sources
+            .kinds
+            .iter()
+            .map(|kind| Box::new(Metered::new(kind.build(client.clone()))) as Box<dyn PriceSource>)
+            .collect();
+        if sources.len() == 1 {
+            sources.remove(0)
+        } else {
+            Box::new(Metered::new(Box::new(Aggregator::new(
+                sources,
+                self.sources.aggregate,
+                self.sources.max_deviation,
+            ))))
+        }
+    }
+
+    /// Where prices come from, e.g. `kraken-ws` or `coinbase,kraken`.
+    pub fn source_name(&self) -> String {
+        if self.stream {
+            return "kraken-ws".to_string();
+        }
+        let names: Vec<_> = self.sources.kinds.iter().map(|kind| kind.name()).collect();
+        names.join(",")
+    }
+
+    pub fn player(&self) -> Result<Player> {
+        Player::new(
+            self.audio.backend,
+            self.audio.device.clone(),
+            self.audio.volume,
+        )
+    }
+
+    /// The `[[notifiers]]`, without the sound player.
+    pub fn notifiers(
+        &self,
+        client: &Client,
+        commands: &mpsc::Sender<CommandRequest>,
+    ) -> Result<Vec<Arc<dyn Notifier>>> {
+        self.notifiers
+            .iter()
+            .map(|notifier| notifier.build(client.clone(), commands))
+            .collect()
+    }
+
+    pub fn watches(&self) -> Vec<Watch> {
+        self.pairs
+            .iter()
+            .map(|pair| Watch::new(pair.pair.clone(), pair.rules(self)))
+            .collect()
+    }
+}
+
+/// Watches `path` and sends every valid new version of the config. Invalid
+/// edits are logged and skipped, so the running config stays in place.
+pub fn watch(path: PathBuf, args: Args) -> Result<mpsc::Receiver<Config>> {
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut watcher = RecommendedWatcher::new(
+        move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|e| !e.kind.is_access()) {
+                let _ = event_tx.send(());
+            }
+        },
+        notify::Config::default(),
+    )?;
+    // Watch the directory, editors replace the file rather than writing it.
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let (config_tx, config_rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        let mut last = fs::read_to_string(&path).ok();
+        while event_rx.recv().await.is_some() {
+            time::sleep(RELOAD_DEBOUNCE).await;
+            while event_rx.try_recv().is_ok() {}
+
+            let text = fs::read_to_string(&path).ok();
+            if text.is_none() || text == last {
+                continue;
+            }
+            last = text;
+
+            let config = Config::read(&path).and_then(|mut config| {
+                config.apply_args(&args);
+                config.validate()?;
+                config.load_sounds()?;
+                Ok(config)
+            });
+            match config {
+                Ok(config) => {
+                    info!("Reloaded {}", path.display());
+                    if config_tx.send(config).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => error!("Keeping previous config: {:#}", e),
+            }
+        }
+    });
+
+    Ok(config_rx)
+}
+
+pub(crate) fn de_threshold<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Threshold {
+        Fraction(f64),
+        Text(String),
+    }
+
+    let fraction = match Threshold::deserialize(deserializer)? {
+        Threshold::Fraction(fraction) => fraction.to_string(),
+        Threshold::Text(text) => text,
+    };
+    parse_threshold(&fraction).map_err(serde::de::Error::custom)
+}
+
+fn de_volume<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<f32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Volume {
+        Fraction(f32),
+        Text(String),
+    }
+
+    let volume = match Volume::deserialize(deserializer)? {
+        Volume::Fraction(fraction) => fraction.to_string(),
+        Volume::Text(text) => text,
+    };
+    parse_volume(&volume).map_err(serde::de::Error::custom)
+}
+
+fn de_opt_threshold<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<f64>, D::Error> {
+    de_threshold(deserializer).map(Some)
+}
+
+fn de_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Duration, D::Error> {
+    parse_interval(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn de_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Duration, D::Error> {
+    humantime::parse_duration(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+pub(crate) fn de_opt_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    de_duration(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn error(text: &str) -> String {
+        format!("{:#}", parse(text).unwrap_err())
+    }
+
+    #[test]
+    fn reads_the_file() {
+        let config = parse(
+            r#"
+            interval = "10s"
+            threshold = "1%"
+
+            [[pairs]]
+            pair = "XBTEUR"
+            threshold = 0.02
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.interval, Duration::from_secs(10));
+        assert_eq!(config.threshold, 0.01);
+        assert_eq!(config.pairs[0].pair, Pair::new("BTC", "EUR"));
+        assert_eq!(config.pairs[0].threshold, Some(0.02));
+    }
+
+    #[test]
+    fn rejects_duplicate_pairs() {
+        let text = r#"
+            [[pairs]]
+            pair = "BTC/USD"
+            [[pairs]]
+            pair = "btc-usd"
+        "#;
+        assert!(error(text).contains("BTC/USD is listed more than once"));
+    }
+
+    fn rule_ids(config: &Config) -> Vec<String> {
+        config.watches()[0]
+            .rules
+            .iter()
+            .map(|rule| rule.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn adds_the_change_rule_only_without_rules_or_with_a_threshold() {
+        let none = parse("[[pairs]]\npair = \"BTC/USD\"").unwrap();
+        assert_eq!(rule_ids(&none), ["0.5% change"]);
+
+        let rules = r#"
+            [[pairs]]
+            pair = "BTC/USD"
+            [[pairs.rules]]
+            move = "2%"
+            within = "1h"
+        "#;
+        assert_eq!(rule_ids(&parse(rules).unwrap()), ["2% within 1h"]);
+
+        let threshold = rules.replace(
+            "pair = \"BTC/USD\"",
+            "pair = \"BTC/USD\"\nthreshold = \"1%\"",
+        );
+        assert_eq!(
+            rule_ids(&parse(&threshold).unwrap()),
+            ["1% change", "2% within 1h"]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_rule_ids() {
+        let text = r#"
+            [[pairs]]
+            pair = "BTC/USD"
+            [[pairs.rules]]
+            id = "fast"
+            move = "2%"
+            within = "1h"
+            [[pairs.rules]]
+            id = "fast"
+            change = "1%"
+        "#;
+        assert!(error(text).contains("Rule id \"fast\" is used more than once"));
+
+        // The implicit change rule is named after its threshold.
+        let text = r#"
+            threshold = "1%"
+            [[pairs]]
+            pair = "BTC/USD"
+            threshold = "1%"
+            [[pairs.rules]]
+            change = "1%"
+        "#;
+        assert!(error(text).contains("is used more than once"));
+    }
+
+    #[test]
+    fn window_rules_need_a_move_and_a_window() {
+        for rule in ["move = \"2%\"", "within = \"1h\""] {
+            let text = format!("[[pairs]]\npair = \"BTC/USD\"\n[[pairs.rules]]\n{}", rule);
+            assert!(error(&text).contains("`within`"), "{}", rule);
+        }
+    }
+
+    fn rule_error(rule: &str) -> String {
+        error(&format!(
+            "[[pairs]]\npair = \"BTC/USD\"\n[[pairs.rules]]\n{}",
+            rule
+        ))
+    }
+
+    #[test]
+    fn rules_have_exactly_one_kind() {
+        for rule in [
+            "sound = \"up.mp3\"",
+            "change = \"1%\"\nabove = 100000.0",
+            "above = 100000.0\nbelow = 90000.0",
+            "cross = 100000.0\nmove = \"2%\"\nwithin = \"1h\"",
+        ] {
+            assert!(rule_error(rule).contains("exactly one of"), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn direction_and_hysteresis_only_apply_to_level_rules() {
+        assert!(rule_error("above = 100000.0\ndirection = \"down\"")
+            .contains("`direction` only applies to `cross` and `every` rules"));
+        assert!(rule_error("change = \"1%\"\nhysteresis = \"0.2%\"")
+            .contains("`hysteresis` and `direction` only apply to level rules"));
+        assert!(rule_error("every = 0.0").contains("must be positive"));
+
+        let config = parse(
+            "[[pairs]]\npair = \"BTC/USD\"\n[[pairs.rules]]\ncross = 100000.0\ndirection = \"up\"\nhysteresis = \"0.2%\"",
+        )
+        .unwrap();
+        let RuleKind::Level {
+            direction,
+            hysteresis,
+            ..
+        } = config.pairs[0].rules[0].kind
+        else {
+            panic!("not a level rule");
+        };
+        assert_eq!((direction, hysteresis), (Some(Direction::Up), 0.002));
+    }
+
+    #[test]
+    fn picks_the_most_specific_sound() {
+        let config = parse(
+            r#"
+            sound = "global.mp3"
+            sound_down = "global-down.mp3"
+
+            [[pairs]]
+            pair = "BTC/USD"
+            sound_up = "pair-up.mp3"
+            [[pairs.rules]]
+            change = "1%"
+            sound = "rule.mp3"
+            [[pairs.rules]]
+            change = "2%"
+
+            [[pairs]]
+            pair = "ETH/USD"
+            "#,
+        )
+        .unwrap();
+        let sounds = |pair: &PairConfig, rule: &RuleConfig| {
+            [Direction::Up, Direction::Down]
+                .map(|direction| pair.sound(rule, &config, direction).unwrap())
+        };
+        let btc = &config.pairs[0];
+        assert_eq!(
+            sounds(btc, &btc.rules[0]),
+            [PathBuf::from("rule.mp3"), PathBuf::from("rule.mp3")]
+        );
+        assert_eq!(
+            sounds(btc, &btc.rules[1]),
+            [
+                PathBuf::from("pair-up.mp3"),
+                PathBuf::from("global-down.mp3")
+            ]
+        );
+
+        let eth = config.pairs[1].clone();
+        let rule = RuleConfig {
+            sound_down: Some(PathBuf::from("rule-down.mp3")),
+            ..btc.rules[1].clone()
+        };
+        assert_eq!(
+            sounds(&eth, &rule),
+            [PathBuf::from("global.mp3"), PathBuf::from("rule-down.mp3")]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{notifier::Alert, rule::Direction};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Something that happened, written to stdout as a line of JSON when events
+/// are on, e.g.
+/// `{"event":"price_tick","pair":"BTC/USD","price":101000.0,"source":"kraken","timestamp":"..."}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    PriceTick {
+        pair: String,
+        price: f64,
+        source: String,
+    },
+    AlertFired {
+        pair: String,
+        rule: String,
+        price: f64,
+        previous_price: f64,
+        change_percent: f64,
+        direction: Direction,
+    },
+    FetchError {
+        source: String,
+        error: String,
+    },
+    SourceSwitched {
+        from: String,
+        to: String,
+    },
+}
+
+impl Event {
+    pub fn alert_fired(alert: &Alert) -> Self {
+        let payload = alert.payload();
+        Event::AlertFired {
+            pair: payload.pair,
+            rule: payload.rule,
+            price: payload.price,
+            previous_price: payload.previous_price,
+            change_percent: payload.change_percent,
+            direction: payload.direction,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    timestamp: String,
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Writes `event` to stdout, if events are on.
+pub fn emit(event: Event) {
+    if !enabled() {
+        return;
+    }
+    let record = Record {
+        event: &event,
+        timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+    };
+    let Ok(line) = serde_json::to_string(&record) else {
+        return;
+    };
+    // A closed pipe, e.g. into `head`, is not worth failing over.
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line).and_then(|()| stdout.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn records_are_tagged_and_timestamped() {
+        let events = [
+            Event::PriceTick {
+                pair: "BTC/USD".to_string(),
+                price: 101000.0,
+                source: "kraken".to_string(),
+            },
+            Event::AlertFired {
+                pair: "BTC/USD".to_string(),
+                rule: "1% change".to_string(),
+                price: 101000.0,
+                previous_price: 100000.0,
+                change_percent: 1.0,
+                direction: Direction::Up,
+            },
+            Event::FetchError {
+                source: "kraken".to_string(),
+                error: "timed out".to_string(),
+            },
+            Event::SourceSwitched {
+                from: "kraken-ws".to_string(),
+                to: "kraken".to_string(),
+            },
+        ];
+        let tags = [
+            "price_tick",
+            "alert_fired",
+            "fetch_error",
+            "source_switched",
+        ];
+        for (event, tag) in events.iter().zip(tags) {
+            let record = Record {
+                event,
+                timestamp: "2026-10-15T10:00:00.000Z".to_string(),
+            };
+            let json: Value = serde_json::to_value(&record).unwrap();
+            assert_eq!(json["event"], tag);
+            assert_eq!(json["timestamp"], "2026-10-15T10:00:00.000Z");
+        }
+
+        let record = Record {
+            event: &events[1],
+            timestamp: "2026-10-15T10:00:00.000Z".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            json!({
+                "event": "alert_fired",
+                "pair": "BTC/USD",
+                "rule": "1% change",
+                "price": 101000.0,
+                "previous_price": 100000.0,
+                "change_percent": 1.0,
+                "direction": "up",
+                "timestamp": "2026-10-15T10:00:00.000Z",
+            })
+        );
+    }
+}
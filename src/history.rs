@@ -0,0 +1,189 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Streaming mode can see hundreds of trades per second, so samples are folded
+// into candles of this length to keep window scans cheap.
+const RESOLUTION: Duration = Duration::from_secs(1);
+
+/// Prices seen within one `RESOLUTION` step.
+#[derive(Debug, Clone, Copy)]
+pub struct Candle {
+    pub start: Instant,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+/// Open, high and low prices over a time window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowStats {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+}
+
+/// Rolling price history for one pair, kept for `retention`.
+#[derive(Debug, Clone)]
+pub struct PriceHistory {
+    candles: VecDeque<Candle>,
+    retention: Duration,
+}
+
+impl PriceHistory {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            candles: VecDeque::new(),
+            retention,
+        }
+    }
+
+    pub fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// Every candle kept, oldest first.
+    pub fn candles(&self) -> impl Iterator<Item = &Candle> {
+        self.candles.iter()
+    }
+
+    pub fn push(&mut self, now: Instant, price: f64) {
+        match self.candles.back_mut() {
+            Some(candle) if now.duration_since(candle.start) < RESOLUTION => {
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+            }
+            _ => self.candles.push_back(Candle {
+                start: now,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+            }),
+        }
+
+        while let Some(candle) = self.candles.front() {
+            if now.duration_since(candle.start) <= self.retention {
+                break;
+            }
+            self.candles.pop_front();
+        }
+    }
+
+    /// Stats over the last `window`, or over everything recorded so far when
+    /// the history is younger than that.
+    pub fn window(&self, now: Instant, window: Duration) -> Option<WindowStats> {
+        let mut candles = self
+            .candles
+            .iter()
+            .filter(|candle| now.duration_since(candle.start) <= window);
+        let first = candles.next()?;
+
+        Some(candles.fold(
+            WindowStats {
+                open: first.open,
+                high: first.high,
+                low: first.low,
+            },
+            |stats, candle| WindowStats {
+                high: stats.high.max(candle.high),
+                low: stats.low.min(candle.low),
+                ..stats
+            },
+        ))
+    }
+
+    /// Up to `points` prices spread evenly over the last `window`, oldest
+    /// first, for drawing the recent trend.
+    pub fn trend(&self, now: Instant, window: Duration, points: u32) -> Vec<f64> {
+        let bucket = window / points;
+        let mut trend: Vec<(u32, f64)> = Vec::new();
+        for candle in &self.candles {
+            let age = now.duration_since(candle.start);
+            if age > window {
+                continue;
+            }
+            let index = ((window - age).as_nanos() / bucket.as_nanos().max(1)) as u32;
+            let index = index.min(points - 1);
+            match trend.last_mut() {
+                Some((last, close)) if *last == index => *close = candle.close,
+                _ => trend.push((index, candle.close)),
+            }
+        }
+        trend.into_iter().map(|(_, close)| close).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn folds_prices_within_the_resolution_into_one_candle() {
+        let start = Instant::now();
+        let mut history = PriceHistory::new(secs(60.0));
+        for (at, price) in [(0.0, 100.0), (0.3, 103.0), (0.6, 98.0), (0.9, 101.0)] {
+            history.push(start + secs(at), price);
+        }
+        history.push(start + secs(1.0), 102.0);
+
+        let candles: Vec<_> = history
+            .candles()
+            .map(|c| (c.open, c.high, c.low, c.close))
+            .collect();
+        assert_eq!(
+            candles,
+            vec![(100.0, 103.0, 98.0, 101.0), (102.0, 102.0, 102.0, 102.0)]
+        );
+    }
+
+    #[test]
+    fn drops_candles_older_than_the_retention() {
+        let start = Instant::now();
+        let mut history = PriceHistory::new(secs(10.0));
+        for at in 0..=12 {
+            history.push(start + secs(at as f64), 100.0 + at as f64);
+        }
+
+        // Candles from 2s to 12s are within 10s of the last push.
+        let opens: Vec<_> = history.candles().map(|c| c.open).collect();
+        assert_eq!(opens.first(), Some(&102.0));
+        assert_eq!(opens.len(), 11);
+    }
+
+    #[test]
+    fn window_covers_only_recent_candles() {
+        let start = Instant::now();
+        let mut history = PriceHistory::new(secs(60.0));
+        for (at, price) in [(0.0, 90.0), (10.0, 100.0), (20.0, 110.0), (30.0, 105.0)] {
+            history.push(start + secs(at), price);
+        }
+        let now = start + secs(30.0);
+
+        let stats = history.window(now, secs(20.0)).unwrap();
+        assert_eq!(
+            stats,
+            WindowStats {
+                open: 100.0,
+                high: 110.0,
+                low: 100.0
+            }
+        );
+        // Younger than the window, so everything counts.
+        assert_eq!(history.window(now, secs(3600.0)).unwrap().low, 90.0);
+        assert!(PriceHistory::new(secs(60.0))
+            .window(now, secs(20.0))
+            .is_none());
+    }
+}
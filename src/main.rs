@@ -1,118 +1,278 @@
-use anyhow::{Context, Result};
-use log::{error, info};
+use anyhow::Result;
+use audio::Player;
+use clap::Parser;
+use cli::Args;
+use config::Config;
+use events::Event;
+use log::{debug, error, info};
+use monitor::PriceMonitor;
+use notifier::Notifier;
+use pair::Pair;
 use reqwest::Client;
-use rodio::{source::Source, Decoder, OutputStream};
-use serde::Deserialize;
-use std::{
-    fs::File,
-    io::BufReader,
-    path::Path,
-    time::{Duration, Instant},
-};
-use tokio::time;
+use source::KrakenStream;
+use std::sync::Arc;
+use tokio::{sync::mpsc, task::JoinHandle, time};
 
-#[derive(Debug, Deserialize)]
-struct KrakenResponse {
-    error: Vec<String>,
-    result: KrakenResult,
-}
-
-#[derive(Debug, Deserialize)]
-struct KrakenResult {
-    #[serde(rename = "XXBTZUSD")]
-    btc_usd: BtcUsdPair,
-}
+mod api;
+mod audio;
+mod cli;
+mod config;
+mod events;
+mod history;
+mod metrics;
+mod monitor;
+mod notifier;
+mod pair;
+mod rule;
+mod sound;
+mod source;
+#[cfg(test)]
+mod testing;
 
-#[derive(Debug, Deserialize)]
-struct BtcUsdPair {
-    c: Vec<String>, // c = last trade closed price
-}
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter_level(args.log_level.into())
+        .parse_default_env()
+        .init();
+    info!("Starting Bitcoin Price Monitor");
 
-struct PriceMonitor {
-    client: Client,
-    last_price: Option<f64>,
-    last_alert: Instant,
-    alert_threshold: f64,
-}
+    let client = Client::builder()
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()?;
 
-impl PriceMonitor {
-    fn new(threshold: f64) -> Self {
-        Self {
-            client: Client::new(),
-            last_price: None,
-            last_alert: Instant::now(),
-            alert_threshold: threshold,
+    let (mut config, config_path) = Config::load(&args)?;
+    let mut reloads = match config_path {
+        Some(path) => {
+            info!("Using config {}", path.display());
+            Some(config::watch(path, args.clone())?)
         }
-    }
+        None => None,
+    };
 
-    async fn fetch_price(&self) -> Result<f64> {
-        let response: KrakenResponse = self
-            .client
-            .get("https://api.kraken.com/0/public/Ticker?pair=XBTUSD")
-            .send()
-            .await?
-            .json()
-            .await?;
+    events::set_enabled(config.events);
+    let mut source = config.source_name();
 
-        if !response.error.is_empty() {
-            anyhow::bail!("Kraken API error: {:?}", response.error);
-        }
+    let (command_tx, mut commands) = mpsc::channel(16);
+    let (query_tx, mut queries) = mpsc::channel(16);
+    let mut api = config
+        .api
+        .map(|addr| {
+            api::serve(
+                addr,
+                config.api_token.clone(),
+                query_tx.clone(),
+                command_tx.clone(),
+            )
+        })
+        .transpose()?;
+    let mut metrics_server = config.metrics.map(api::serve_metrics).transpose()?;
+    let mut player = config.player()?;
+    let mut notifiers = config.notifiers(&client, &command_tx)?;
+    let mut monitor = PriceMonitor::new(
+        config.source(&client),
+        config.watches(),
+        with_player(&player, &notifiers),
+    );
+    info!(
+        "Watching {} using {}",
+        monitor
+            .pairs()
+            .iter()
+            .map(Pair::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        monitor.source.name()
+    );
 
-        let price = response.result.btc_usd.c[0]
-            .parse::<f64>()
-            .context("Failed to parse price")?;
+    let (trade_tx, mut trades) = mpsc::channel(256);
+    let mut stream = config
+        .stream
+        .then(|| spawn_stream(&config, monitor.pairs(), trade_tx.clone()));
+    let mut interval_timer = time::interval(config.interval);
 
-        Ok(price)
-    }
+    loop {
+        tokio::select! {
+            _ = interval_timer.tick() => {
+                if stream.is_none() {
+                    poll(&mut monitor, &source).await;
+                }
+                monitor.snapshot();
+            }
+            Some((pair, current_price)) = trades.recv() => {
+                debug!("{} trade at {:.2}", pair, current_price);
+                metrics::PRICE
+                    .with_label_values(&[pair.to_string().as_str(), source.as_str()])
+                    .set(current_price);
+                events::emit(Event::PriceTick {
+                    pair: pair.to_string(),
+                    price: current_price,
+                    source: source.clone(),
+                });
+                monitor.observe(&pair, current_price);
+            }
+            Some((command, reply)) = commands.recv() => {
+                let _ = reply.send(monitor.handle(command));
+            }
+            Some((query, reply)) = queries.recv() => {
+                let _ = reply.send(monitor.query(query));
+            }
+            Some(new_config) = next_reload(&mut reloads) => {
+                let new_player = if new_config.audio != config.audio {
+                    new_config.player().unwrap_or_else(|e| {
+                        error!("Keeping previous audio output: {:#}", e);
+                        player.clone()
+                    })
+                } else {
+                    player.clone()
+                };
+                // Keep the running notifiers unless they changed, rather than
+                // reconnecting to MQTT or losing a pending email digest.
+                if new_config.notifiers != config.notifiers {
+                    match new_config.notifiers(&client, &command_tx) {
+                        Ok(new_notifiers) => notifiers = new_notifiers,
+                        Err(e) => {
+                            error!("Keeping previous config: {:#}", e);
+                            continue;
+                        }
+                    }
+                }
+                player = new_player;
+                if new_config.api != config.api || new_config.api_token != config.api_token {
+                    // A new token on the same address needs the port back first.
+                    if new_config.api == config.api {
+                        if let Some(task) = api.take() {
+                            task.abort();
+                            let _ = task.await;
+                        }
+                    }
+                    let new_api = new_config
+                        .api
+                        .map(|addr| {
+                            api::serve(
+                                addr,
+                                new_config.api_token.clone(),
+                                query_tx.clone(),
+                                command_tx.clone(),
+                            )
+                        })
+                        .transpose();
+                    replace_server(&mut api, new_api, "HTTP API");
+                }
+                if new_config.metrics != config.metrics {
+                    let new_server = new_config.metrics.map(api::serve_metrics).transpose();
+                    replace_server(&mut metrics_server, new_server, "metrics server");
+                }
+                events::set_enabled(new_config.events);
+                let new_source = new_config.source_name();
+                if new_source != source {
+                    info!("Switching source from {} to {}", source, new_source);
+                    events::emit(Event::SourceSwitched {
+                        from: source,
+                        to: new_source.clone(),
+                    });
+                    source = new_source;
+                }
+                monitor.reconfigure(
+                    new_config.source(&client),
+                    new_config.watches(),
+                    with_player(&player, &notifiers),
+                );
+                if new_config.interval != config.interval {
+                    interval_timer = time::interval(new_config.interval);
+                }
 
-    fn should_alert(&self, current_price: f64) -> bool {
-        if let Some(last_price) = self.last_price {
-            let price_change = (current_price - last_price).abs() / last_price;
-            price_change >= self.alert_threshold
-        } else {
-            false
+                // Resubscribe when the streamed pairs or endpoint change.
+                let pairs: Vec<_> = new_config.pairs.iter().map(|p| &p.pair).collect();
+                let old_pairs: Vec<_> = config.pairs.iter().map(|p| &p.pair).collect();
+                if new_config.stream != config.stream
+                    || new_config.ws_url != config.ws_url
+                    || pairs != old_pairs
+                {
+                    if let Some(task) = stream.take() {
+                        task.abort();
+                    }
+                    stream = new_config
+                        .stream
+                        .then(|| spawn_stream(&new_config, monitor.pairs(), trade_tx.clone()));
+                }
+                config = new_config;
+            }
         }
     }
+}
 
-    fn play_alert(&self) -> Result<()> {
-        let (_stream, stream_handle) = OutputStream::try_default()?;
-        let file = File::open(Path::new("src/alert.mp3"))?;
-        let source = Decoder::new(BufReader::new(file))?;
-        stream_handle.play_raw(source.convert_samples())?;
-        std::thread::sleep(Duration::from_secs(1)); // Wait for sound to play
-        Ok(())
+async fn poll(monitor: &mut PriceMonitor, source: &str) {
+    match monitor.fetch_prices().await {
+        Ok(quotes) => {
+            for quote in quotes {
+                if events::enabled() {
+                    events::emit(Event::PriceTick {
+                        pair: quote.pair.to_string(),
+                        price: quote.price,
+                        source: source.to_string(),
+                    });
+                } else {
+                    info!("Current {} price: {:.2}", quote.pair, quote.price);
+                }
+                monitor.observe(&quote.pair, quote.price);
+            }
+        }
+        Err(e) => {
+            error!(
+                "Failed to fetch prices from {}: {}",
+                monitor.source.name(),
+                e
+            );
+            events::emit(Event::FetchError {
+                source: source.to_string(),
+                error: e.to_string(),
+            });
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    env_logger::init();
-    info!("Starting Bitcoin Price Monitor");
-
-    let mut monitor = PriceMonitor::new(0.00001); // 0.5% threshold
-    let interval = Duration::from_secs(5); // 5 minutes
-
-    let mut interval_timer = time::interval(interval);
-    loop {
-        interval_timer.tick().await;
-
-        match monitor.fetch_price().await {
-            Ok(current_price) => {
-                info!("Current BTC price: ${:.2}", current_price);
+fn spawn_stream(
+    config: &Config,
+    pairs: Vec<Pair>,
+    trades: mpsc::Sender<(Pair, f64)>,
+) -> JoinHandle<()> {
+    let stream = KrakenStream::new(config.ws_url.clone(), pairs);
+    tokio::spawn(async move { stream.run(trades).await })
+}
 
-                if monitor.should_alert(current_price) {
-                    info!("Price change threshold reached! Playing alert...");
-                    if let Err(e) = monitor.play_alert() {
-                        error!("Failed to play alert sound: {}", e);
-                    }
-                    monitor.last_alert = Instant::now();
-                }
+// The sound player, followed by the configured notifiers.
+fn with_player(player: &Player, notifiers: &[Arc<dyn Notifier>]) -> Vec<Arc<dyn Notifier>> {
+    let mut all: Vec<Arc<dyn Notifier>> = vec![Arc::new(player.clone())];
+    all.extend(notifiers.iter().cloned());
+    all
+}
 
-                monitor.last_price = Some(current_price);
-            }
-            Err(e) => {
-                error!("Failed to fetch price: {}", e);
+// Swaps in a server started for a reloaded address, keeping the running one
+// if the new one failed to start.
+fn replace_server(
+    server: &mut Option<JoinHandle<()>>,
+    started: Result<Option<JoinHandle<()>>>,
+    what: &str,
+) {
+    match started {
+        Ok(new_server) => {
+            if let Some(task) = std::mem::replace(server, new_server) {
+                task.abort();
             }
         }
+        Err(e) => error!("Keeping previous {}: {:#}", what, e),
+    }
+}
+
+async fn next_reload(reloads: &mut Option<mpsc::Receiver<Config>>) -> Option<Config> {
+    match reloads {
+        Some(reloads) => reloads.recv().await,
+        None => std::future::pending().await,
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,80 @@
+use prometheus::{
+    register_counter_vec, register_gauge_vec, register_histogram_vec, CounterVec, Encoder,
+    GaugeVec, HistogramVec, TextEncoder,
+};
+use std::sync::LazyLock;
+
+pub static PRICE: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "subtle_alert_price",
+        "Last price seen for a pair",
+        &["pair", "source"]
+    )
+    .unwrap()
+});
+
+pub static FETCHES: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "subtle_alert_fetches_total",
+        "Price fetches from a source",
+        &["source"]
+    )
+    .unwrap()
+});
+
+pub static FETCH_ERRORS: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "subtle_alert_fetch_errors_total",
+        "Failed price fetches from a source, by kind of error",
+        &["source", "kind"]
+    )
+    .unwrap()
+});
+
+pub static FETCH_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "subtle_alert_fetch_duration_seconds",
+        "Time taken to fetch prices from a source",
+        &["source"]
+    )
+    .unwrap()
+});
+
+pub static ALERTS: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "subtle_alert_alerts_total",
+        "Alerts fired, by pair and rule",
+        &["pair", "rule"]
+    )
+    .unwrap()
+});
+
+/// Every metric in the Prometheus text format.
+pub fn render() -> String {
+    let mut text = Vec::new();
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut text);
+    String::from_utf8(text).unwrap_or_default()
+}
+
+/// Counts a failed fetch from `source`.
+pub fn fetch_failed(source: &str, e: &anyhow::Error) {
+    FETCH_ERRORS
+        .with_label_values(&[source, error_kind(e)])
+        .inc();
+}
+
+/// What went wrong in a failed fetch, for the `kind` label.
+pub fn error_kind(e: &anyhow::Error) -> &'static str {
+    match e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+    {
+        Some(e) if e.is_timeout() => "timeout",
+        Some(e) if e.is_connect() => "connect",
+        Some(e) if e.is_status() => "status",
+        Some(e) if e.is_decode() => "decode",
+        Some(_) => "request",
+        // The exchange answered, but with an error or nothing usable.
+        None => "response",
+    }
+}
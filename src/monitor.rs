@@ -0,0 +1,569 @@
+use crate::{
+    events::{self, Event},
+    history::PriceHistory,
+    metrics,
+    notifier::{Alert, Notifier},
+    pair::Pair,
+    rule::{Rule, RuleKind},
+    source::{PriceSource, Quote},
+};
+use anyhow::Result;
+use log::{debug, error, info};
+use serde_json::{json, Value};
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::oneshot;
+
+// Alerts carry the trend over this long, so notifiers can draw it.
+const TREND_WINDOW: Duration = Duration::from_secs(15 * 60);
+const TREND_POINTS: u32 = 20;
+
+// Health checks fail once a pair has gone this long without a price.
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Alert rules and price state for one watched pair.
+pub struct Watch {
+    pub pair: Pair,
+    pub rules: Vec<Rule>,
+    history: PriceHistory,
+    last_price: Option<f64>,
+    // Price at the last interval, which change rules compare against.
+    reference: Option<f64>,
+    updated: Option<Instant>,
+}
+
+impl Watch {
+    pub fn new(pair: Pair, rules: Vec<Rule>) -> Self {
+        let lookback = rules.iter().map(|rule| rule.kind.lookback()).max();
+        Self {
+            pair,
+            rules,
+            history: PriceHistory::new(lookback.unwrap_or_default().max(TREND_WINDOW)),
+            last_price: None,
+            reference: None,
+            updated: None,
+        }
+    }
+
+    // Takes over the price state of the watch this one replaces.
+    fn inherit(&mut self, old: Watch) {
+        let retention = self.history.retention();
+        self.history = old.history;
+        self.history.set_retention(retention);
+        self.last_price = old.last_price;
+        self.reference = old.reference;
+        self.updated = old.updated;
+        for rule in &mut self.rules {
+            if let Some(old_rule) = old.rules.iter().find(|r| r.id == rule.id) {
+                rule.inherit(old_rule);
+            }
+        }
+    }
+}
+
+/// A request to change or report on the running monitor, e.g. from a chat
+/// bot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// The last price of every pair.
+    Price,
+    /// Sets the threshold of the change rules of `pair`, or of every pair.
+    Threshold {
+        threshold: f64,
+        pair: Option<Pair>,
+    },
+    /// Silences alerts for a while, or lifts the mute with `None`.
+    Mute(Option<Duration>),
+    Status,
+}
+
+/// A [`Command`] and where to send its reply.
+pub type CommandRequest = (Command, oneshot::Sender<String>);
+
+/// A request for the state of the running monitor, answered with JSON for
+/// the HTTP API.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Price,
+    /// Recent candles of `pair`, or of every pair.
+    History(Option<Pair>),
+    Rules,
+    Health,
+}
+
+/// A [`Query`] and where to send its answer.
+pub type QueryRequest = (Query, oneshot::Sender<Value>);
+
+pub struct PriceMonitor {
+    pub source: Box<dyn PriceSource>,
+    pub watches: Vec<Watch>,
+    pub notifiers: Vec<Arc<dyn Notifier>>,
+    muted_until: Option<Instant>,
+}
+
+impl PriceMonitor {
+    pub fn new(
+        source: Box<dyn PriceSource>,
+        watches: Vec<Watch>,
+        notifiers: Vec<Arc<dyn Notifier>>,
+    ) -> Self {
+        Self {
+            source,
+            watches,
+            notifiers,
+            muted_until: None,
+        }
+    }
+
+    /// Swaps in a new source, watch list and notifiers, keeping the price
+    /// state of pairs that are still watched.
+    pub fn reconfigure(
+        &mut self,
+        source: Box<dyn PriceSource>,
+        mut watches: Vec<Watch>,
+        notifiers: Vec<Arc<dyn Notifier>>,
+    ) {
+        for old in self.watches.drain(..) {
+            if let Some(watch) = watches.iter_mut().find(|w| w.pair == old.pair) {
+                watch.inherit(old);
+            }
+        }
+        self.source = source;
+        self.watches = watches;
+        self.notifiers = notifiers;
+    }
+
+    pub fn pairs(&self) -> Vec<Pair> {
+        self.watches.iter().map(|w| w.pair.clone()).collect()
+    }
+
+    pub async fn fetch_prices(&self) -> Result<Vec<Quote>> {
+        self.source.fetch_quotes(&self.pairs()).await
+    }
+
+    pub fn observe(&mut self, pair: &Pair, current_price: f64) {
+        let Some(watch) = self.watches.iter_mut().find(|w| &w.pair == pair) else {
+            return;
+        };
+
+        let now = Instant::now();
+        watch.history.push(now, current_price);
+        for notifier in &self.notifiers {
+            notifier.price(pair, current_price);
+        }
+        let muted = self.muted_until.is_some_and(|until| now < until);
+
+        for rule in &mut watch.rules {
+            let Some(change) = rule.should_alert(
+                watch.reference,
+                watch.last_price,
+                current_price,
+                &watch.history,
+                now,
+            ) else {
+                continue;
+            };
+
+            if muted {
+                info!(
+                    "{} rule {} reached, {}, but alerts are muted",
+                    pair, rule.id, change
+                );
+            } else if rule.cooldown.allows(change.direction, current_price, now) {
+                info!("{} rule {} reached, {}!", pair, rule.id, change);
+                let alert = Alert {
+                    pair: pair.clone(),
+                    rule: rule.id.clone(),
+                    change,
+                    sound: rule.sound(change.direction).clone(),
+                    trend: watch.history.trend(now, TREND_WINDOW, TREND_POINTS),
+                    time: SystemTime::now(),
+                };
+                events::emit(Event::alert_fired(&alert));
+                metrics::ALERTS
+                    .with_label_values(&[pair.to_string().as_str(), rule.id.as_str()])
+                    .inc();
+                notify(&self.notifiers, alert);
+                rule.cooldown.record(change.direction, current_price, now);
+            } else {
+                debug!(
+                    "{} rule {} suppressed, {:?} move is in cooldown",
+                    pair, rule.id, change.direction
+                );
+            }
+        }
+
+        watch.last_price = Some(current_price);
+        watch.updated = Some(now);
+    }
+
+    /// Makes the last price of every pair the one change rules compare
+    /// against, once per interval.
+    pub fn snapshot(&mut self) {
+        for watch in &mut self.watches {
+            if watch.last_price.is_some() {
+                watch.reference = watch.last_price;
+            }
+        }
+    }
+
+    /// Carries out `command` and returns a reply for the user.
+    pub fn handle(&mut self, command: Command) -> String {
+        match command {
+            Command::Price => self
+                .watches
+                .iter()
+                .map(|watch| match watch.last_price {
+                    Some(price) => format!("{} {:.2}", watch.pair, price),
+                    None => format!("{} no price yet", watch.pair),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Command::Threshold { threshold, pair } => {
+                let kind = RuleKind::Change { threshold };
+                let mut changed = Vec::new();
+                for watch in &mut self.watches {
+                    if pair.as_ref().is_some_and(|pair| pair != &watch.pair) {
+                        continue;
+                    }
+                    let mut any = false;
+                    for rule in &mut watch.rules {
+                        if matches!(rule.kind, RuleKind::Change { .. }) {
+                            // Keep ids named after the threshold up to date.
+                            if rule.id == rule.kind.to_string() {
+                                rule.id = kind.to_string();
+                            }
+                            rule.kind = kind.clone();
+                            any = true;
+                        }
+                    }
+                    if any {
+                        changed.push(watch.pair.to_string());
+                    }
+                }
+                if changed.is_empty() {
+                    return "No change rule to update".to_string();
+                }
+                let reply = format!("Alerting on {} for {}", kind, changed.join(", "));
+                info!("{}", reply);
+                reply
+            }
+            Command::Mute(Some(duration)) => {
+                self.muted_until = Some(Instant::now() + duration);
+                let reply = format!("Alerts muted for {}", humantime::format_duration(duration));
+                info!("{}", reply);
+                reply
+            }
+            Command::Mute(None) => {
+                self.muted_until = None;
+                info!("Alerts unmuted");
+                "Alerts unmuted".to_string()
+            }
+            Command::Status => self.status(),
+        }
+    }
+
+    /// Answers `query` from the current state.
+    pub fn query(&self, query: Query) -> Value {
+        let now = Instant::now();
+        match query {
+            Query::Price => self
+                .watches
+                .iter()
+                .map(|watch| {
+                    json!({
+                        "pair": watch.pair.to_string(),
+                        "price": watch.last_price,
+                        "updated": watch.updated.map(|at| timestamp(now, at)),
+                    })
+                })
+                .collect(),
+            Query::History(pair) => self
+                .watches
+                .iter()
+                .filter(|watch| pair.as_ref().is_none_or(|pair| pair == &watch.pair))
+                .map(|watch| {
+                    let candles: Vec<_> = watch
+                        .history
+                        .candles()
+                        .map(|candle| {
+                            json!({
+                                "time": timestamp(now, candle.start),
+                                "open": candle.open,
+                                "high": candle.high,
+                                "low": candle.low,
+                                "close": candle.close,
+                            })
+                        })
+                        .collect();
+                    json!({ "pair": watch.pair.to_string(), "candles": candles })
+                })
+                .collect(),
+            Query::Rules => self
+                .watches
+                .iter()
+                .map(|watch| {
+                    let rules: Vec<_> = watch
+                        .rules
+                        .iter()
+                        .map(|rule| json!({ "id": rule.id, "rule": rule.kind.to_string() }))
+                        .collect();
+                    json!({ "pair": watch.pair.to_string(), "rules": rules })
+                })
+                .collect(),
+            Query::Health => {
+                let fresh = self.watches.iter().all(|watch| {
+                    watch
+                        .updated
+                        .is_some_and(|at| now.duration_since(at) < STALE_AFTER)
+                });
+                let pairs: Vec<_> = self
+                    .watches
+                    .iter()
+                    .map(|watch| {
+                        json!({
+                            "pair": watch.pair.to_string(),
+                            "age_secs": watch.updated.map(|at| now.duration_since(at).as_secs()),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "status": if fresh { "ok" } else { "stale" },
+                    "source": self.source.name(),
+                    "muted": self.muted_until.is_some_and(|until| now < until),
+                    "pairs": pairs,
+                })
+            }
+        }
+    }
+
+    fn status(&self) -> String {
+        let mut status = format!("Source: {}", self.source.name());
+        for watch in &self.watches {
+            let price = match watch.last_price {
+                Some(price) => format!("{:.2}", price),
+                None => "no price yet".to_string(),
+            };
+            let rules: Vec<_> = watch
+                .rules
+                .iter()
+                .map(|rule| rule.kind.to_string())
+                .collect();
+            let _ = write!(status, "\n{} {}: {}", watch.pair, price, rules.join(", "));
+        }
+        match self.muted_until {
+            Some(until) if until > Instant::now() => {
+                let left = Duration::from_secs((until - Instant::now()).as_secs());
+                let _ = write!(status, "\nMuted for {}", humantime::format_duration(left));
+            }
+            _ => status.push_str("\nNot muted"),
+        }
+        let notifiers: Vec<_> = self.notifiers.iter().map(|n| n.name()).collect();
+        let _ = write!(status, "\nNotifying {}", notifiers.join(", "));
+        status
+    }
+}
+
+// Wall clock time of `at`, as RFC 3339.
+fn timestamp(now: Instant, at: Instant) -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now() - now.duration_since(at)).to_string()
+}
+
+// Delivers `alert` to every notifier in the background, so a slow webhook
+// does not hold up the next price.
+fn notify(notifiers: &[Arc<dyn Notifier>], alert: Alert) {
+    let alert = Arc::new(alert);
+    for notifier in notifiers {
+        let notifier = notifier.clone();
+        let alert = alert.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.notify(&alert).await {
+                error!("Failed to send {} notification: {:#}", notifier.name(), e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::{AudioBackend, Player},
+        rule::{Cooldown, Direction},
+        sound::Sound,
+    };
+    use async_trait::async_trait;
+    use tokio::time;
+
+    struct NoSource;
+
+    #[async_trait]
+    impl PriceSource for NoSource {
+        fn name(&self) -> &'static str {
+            "none"
+        }
+
+        async fn fetch_quote(&self, _pair: &Pair) -> Result<Quote> {
+            anyhow::bail!("No quotes in tests")
+        }
+    }
+
+    fn btc() -> Pair {
+        Pair::new("BTC", "USD")
+    }
+
+    fn monitor(rules: Vec<Rule>) -> (PriceMonitor, Player) {
+        let player = Player::new(AudioBackend::Null, None, 1.0).unwrap();
+        let monitor = PriceMonitor::new(
+            Box::new(NoSource),
+            vec![Watch::new(btc(), rules)],
+            vec![Arc::new(player.clone())],
+        );
+        (monitor, player)
+    }
+
+    fn change_rule(threshold: f64, cooldown: Duration) -> Rule {
+        let kind = RuleKind::Change { threshold };
+        Rule::new(
+            kind.to_string(),
+            kind,
+            Sound::Tone,
+            Sound::default(),
+            Cooldown::new(cooldown, None),
+        )
+    }
+
+    // Lets the spawned notification tasks run.
+    async fn settle() {
+        time::sleep(Duration::from_millis(20)).await;
+    }
+
+    fn directions(player: &Player) -> Vec<Direction> {
+        player
+            .played()
+            .iter()
+            .map(|(_, change)| change.direction)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn change_spread_over_many_trades_alerts() {
+        let (mut monitor, player) = monitor(vec![change_rule(0.01, Duration::from_secs(60))]);
+        monitor.observe(&btc(), 100.0);
+        monitor.snapshot();
+
+        // A 1.1% drop in steps far below the threshold, within one interval.
+        let mut price = 100.0;
+        for _ in 0..220 {
+            price -= 0.005;
+            monitor.observe(&btc(), price);
+        }
+        settle().await;
+
+        let played = player.played();
+        assert_eq!(played.len(), 1);
+        assert_eq!(played[0].1.direction, Direction::Down);
+        assert_eq!(played[0].1.from, 100.0);
+        assert!(played[0].1.change() <= -0.01);
+    }
+
+    #[tokio::test]
+    async fn change_compares_with_the_last_snapshot() {
+        let (mut monitor, player) = monitor(vec![change_rule(0.01, Duration::ZERO)]);
+        // No reference yet.
+        monitor.observe(&btc(), 100.0);
+        monitor.observe(&btc(), 102.0);
+        monitor.snapshot();
+        // 0.98% from 102, below the threshold.
+        monitor.observe(&btc(), 101.0);
+        monitor.snapshot();
+        monitor.observe(&btc(), 99.9);
+        settle().await;
+
+        assert_eq!(directions(&player), vec![Direction::Down]);
+        assert_eq!(player.played()[0].1.from, 101.0);
+    }
+
+    #[tokio::test]
+    async fn plays_the_sound_for_the_direction_outside_cooldown() {
+        let (mut monitor, player) = monitor(vec![change_rule(0.01, Duration::from_secs(60))]);
+        for price in [100.0, 102.0, 104.0, 101.0, 99.0] {
+            monitor.observe(&btc(), price);
+            monitor.snapshot();
+        }
+        settle().await;
+
+        // 104 is a second rise within the cooldown, 99 a second drop.
+        let played = player.played();
+        let moves: Vec<_> = played
+            .iter()
+            .map(|(sound, change)| (sound.clone(), change.from, change.to))
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                (Sound::Tone, 100.0, 102.0),
+                (Sound::default(), 104.0, 101.0)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn muted_alerts_are_not_played() {
+        let (mut monitor, player) = monitor(vec![change_rule(0.01, Duration::ZERO)]);
+        monitor.handle(Command::Mute(Some(Duration::from_secs(60))));
+        monitor.observe(&btc(), 100.0);
+        monitor.snapshot();
+        monitor.observe(&btc(), 90.0);
+        settle().await;
+        assert!(player.played().is_empty());
+
+        monitor.handle(Command::Mute(None));
+        monitor.snapshot();
+        monitor.observe(&btc(), 80.0);
+        settle().await;
+        assert_eq!(directions(&player), vec![Direction::Down]);
+    }
+
+    #[tokio::test]
+    async fn reconfigure_keeps_state_for_pairs_still_watched() {
+        let eth = Pair::new("ETH", "USD");
+        let (mut monitor, player) = monitor(vec![change_rule(0.01, Duration::ZERO)]);
+        monitor.reconfigure(
+            Box::new(NoSource),
+            vec![
+                Watch::new(btc(), Vec::new()),
+                Watch::new(eth.clone(), Vec::new()),
+            ],
+            vec![Arc::new(player.clone())],
+        );
+        monitor.observe(&btc(), 100.0);
+        monitor.observe(&eth, 2000.0);
+
+        monitor.reconfigure(
+            Box::new(NoSource),
+            vec![
+                Watch::new(btc(), vec![change_rule(0.01, Duration::ZERO)]),
+                Watch::new(Pair::new("SOL", "USD"), Vec::new()),
+            ],
+            vec![Arc::new(player.clone())],
+        );
+        assert_eq!(monitor.pairs(), [btc(), Pair::new("SOL", "USD")]);
+        let watch = &monitor.watches[0];
+        assert_eq!(watch.last_price, Some(100.0));
+        assert_eq!(watch.history.candles().count(), 1);
+        assert_eq!(monitor.watches[1].last_price, None);
+
+        // A dropped pair starts over when it is watched again.
+        monitor.reconfigure(
+            Box::new(NoSource),
+            vec![Watch::new(eth, Vec::new())],
+            vec![Arc::new(player.clone())],
+        );
+        assert_eq!(monitor.watches[0].last_price, None);
+        assert_eq!(monitor.watches[0].history.candles().count(), 0);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::cli::parse_interval;
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use {
+    super::{Alert, Notifier},
+    crate::monitor::{Command, CommandRequest},
+    anyhow::Result,
+    async_trait::async_trait,
+    tokio::sync::mpsc,
+};
+
+const DEFAULT_SNOOZE: Duration = Duration::from_secs(15 * 60);
+/// How long a notification's snooze button is listened for before the
+/// notification is closed.
+#[cfg(target_os = "linux")]
+const ACTION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DesktopConfig {
+    /// How long the notification's snooze button mutes alerts for.
+    #[serde(default = "default_snooze", deserialize_with = "de_snooze")]
+    pub snooze: Duration,
+}
+
+fn default_snooze() -> Duration {
+    DEFAULT_SNOOZE
+}
+
+fn de_snooze<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Duration, D::Error> {
+    parse_interval(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+/// Shows alerts as freedesktop notifications over D-Bus, with a button that
+/// mutes alerts for a while.
+#[cfg(target_os = "linux")]
+pub struct Desktop {
+    snooze: Duration,
+    commands: mpsc::Sender<CommandRequest>,
+}
+
+#[cfg(target_os = "linux")]
+impl Desktop {
+    pub fn new(config: &DesktopConfig, commands: mpsc::Sender<CommandRequest>) -> Self {
+        Self {
+            snooze: config.snooze,
+            commands,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn urgency(magnitude: u8) -> notify_rust::Urgency {
+    use notify_rust::Urgency;
+    match magnitude {
+        1 | 2 => Urgency::Low,
+        3 | 4 => Urgency::Normal,
+        _ => Urgency::Critical,
+    }
+}
+
+// One line per field, without the markdown code quotes chat messages use.
+#[cfg(target_os = "linux")]
+fn body(alert: &Alert) -> String {
+    alert
+        .fields()
+        .into_iter()
+        .map(|(title, value)| format!("{}: {}", title, value.trim_matches('`')))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(target_os = "linux")]
+#[async_trait]
+impl Notifier for Desktop {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        use notify_rust::{Notification, NotificationResponse};
+        use tokio::{sync::oneshot, time};
+
+        let mut notification = Notification::new();
+        notification
+            .appname(env!("CARGO_PKG_NAME"))
+            .summary(&alert.headline())
+            .body(&body(alert))
+            .urgency(urgency(alert.change.magnitude()))
+            .action(
+                "snooze",
+                &format!("Snooze {}", humantime::format_duration(self.snooze)),
+            );
+
+        let handle = notification.show_async().await?;
+
+        // Wait for a click in the background rather than holding up the
+        // alert, and give up on notifications left open too long.
+        let snooze = self.snooze;
+        let commands = self.commands.clone();
+        tokio::spawn(async move {
+            let clicked = handle.wait_for_action_async(|response| {
+                if matches!(response, NotificationResponse::Action(action) if action == "snooze") {
+                    let (reply, _) = oneshot::channel();
+                    let _ = commands.try_send((Command::Mute(Some(snooze)), reply));
+                }
+            });
+            if time::timeout(ACTION_TIMEOUT, clicked).await.is_err() {
+                handle.close_async().await;
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound};
+    use notify_rust::Urgency;
+    use std::time::SystemTime;
+
+    fn alert(to: f64, trend: Vec<f64>) -> Alert {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(100.0, to),
+            sound: Sound::default(),
+            trend,
+            time: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn urgency_follows_the_move() {
+        for (to, expected) in [
+            (100.4999, Urgency::Low),
+            (100.5, Urgency::Low),
+            (101.0, Urgency::Normal),
+            (98.0, Urgency::Normal),
+            (105.0, Urgency::Critical),
+        ] {
+            assert_eq!(
+                urgency(Move::new(100.0, to).magnitude()),
+                expected,
+                "{}",
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn body_lists_the_fields() {
+        assert_eq!(
+            body(&alert(101.0, vec![100.0, 101.0])),
+            "Price: 101.00\nChange: +1.00 (+1.00%)\nPrevious: 100.00\nRule: 1% change\nTrend: ▁█"
+        );
+    }
+
+    // Needs a desktop session with a notification server.
+    #[tokio::test]
+    #[ignore]
+    async fn shows_a_notification_on_the_session_bus() {
+        let (commands, _) = mpsc::channel(1);
+        let config = DesktopConfig {
+            snooze: DEFAULT_SNOOZE,
+        };
+        Desktop::new(&config, commands)
+            .notify(&alert(101.0, vec![100.0, 101.0]))
+            .await
+            .unwrap();
+    }
+}
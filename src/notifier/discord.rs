@@ -0,0 +1,113 @@
+use super::{check_url, json_headers, post, Alert, ChatConfig, Notifier};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+/// Posts alerts to a Discord webhook as embeds.
+pub struct Discord {
+    client: Client,
+    config: ChatConfig,
+}
+
+impl Discord {
+    pub fn new(client: Client, config: ChatConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        if config.channel.is_some() {
+            anyhow::bail!("Discord webhooks always post to their own channel");
+        }
+        Ok(Self { client, config })
+    }
+}
+
+fn message(alert: &Alert, config: &ChatConfig) -> Value {
+    let fields: Vec<_> = alert
+        .fields()
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value, "inline": name != "Trend" }))
+        .collect();
+    let mut message = json!({
+        "embeds": [{
+            "title": alert.headline(),
+            "color": alert.color(),
+            "fields": fields,
+            "timestamp": alert.timestamp(),
+        }],
+    });
+    if let Some(username) = &config.username {
+        message["username"] = json!(username);
+    }
+    message
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = message(alert, &self.config).to_string();
+        post(
+            &self.client,
+            &self.config.url,
+            &json_headers(),
+            body,
+            self.config.retries,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound};
+    use std::time::{Duration, SystemTime};
+
+    fn config(channel: Option<&str>) -> ChatConfig {
+        ChatConfig {
+            url: "https://discord.com/api/webhooks/1/X".to_string(),
+            username: Some("subtle-alert".to_string()),
+            channel: channel.map(str::to_string),
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn formats_an_embed() {
+        let alert = Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(101000.0, 99990.0),
+            sound: Sound::default(),
+            trend: vec![101000.0, 99990.0],
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        // Discord has no channel override, the webhook decides.
+        assert_eq!(
+            message(&alert, &config(Some("#prices"))),
+            json!({
+                "username": "subtle-alert",
+                "embeds": [{
+                    "title": "▼ BTC/USD -1.00% to 99990.00",
+                    "color": 0xe01e5a,
+                    "fields": [
+                        { "name": "Price", "value": "99990.00", "inline": true },
+                        { "name": "Change", "value": "-1010.00 (-1.00%)", "inline": true },
+                        { "name": "Previous", "value": "101000.00", "inline": true },
+                        { "name": "Rule", "value": "1% change", "inline": true },
+                        { "name": "Trend", "value": "`█▁`", "inline": false },
+                    ],
+                    "timestamp": "2023-11-14T22:13:20Z",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_channel() {
+        assert!(Discord::new(Client::new(), config(Some("#prices"))).is_err());
+        assert!(Discord::new(Client::new(), config(None)).is_ok());
+    }
+}
@@ -0,0 +1,378 @@
+use super::{default_retries, retry, Alert, Notifier};
+use crate::{cli::parse_interval, pair::Pair};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use log::{error, info};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS, port 587 by default.
+    #[default]
+    Starttls,
+    /// TLS from the start, port 465 by default.
+    Tls,
+    /// No encryption, port 25 by default. Only for a local relay or sink.
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Send one message with every alert from this long instead of one per
+    /// alert, e.g. "15m".
+    #[serde(default, deserialize_with = "de_digest")]
+    pub digest: Option<Duration>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn de_digest<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    parse_interval(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Emails alerts over SMTP, one by one or batched into a digest.
+pub struct Email {
+    mailer: Arc<Mailer>,
+    pending: Arc<Mutex<Digest>>,
+    digest: Option<JoinHandle<()>>,
+}
+
+// What the next digest reports.
+#[derive(Default)]
+struct Digest {
+    alerts: Vec<Alert>,
+    // Lowest and highest price seen for each pair.
+    ranges: BTreeMap<String, (f64, f64)>,
+}
+
+impl Digest {
+    fn see(&mut self, pair: &Pair, price: f64) {
+        let (low, high) = self
+            .ranges
+            .entry(pair.to_string())
+            .or_insert((f64::INFINITY, f64::NEG_INFINITY));
+        *low = low.min(price);
+        *high = high.max(price);
+    }
+}
+
+struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    retries: u32,
+}
+
+impl Email {
+    pub fn new(config: &EmailConfig) -> Result<Self> {
+        let mut transport = match config.tls {
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        if let Some(port) = config.port {
+            transport = transport.port(port);
+        }
+        match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                transport =
+                    transport.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+            (None, None) => {}
+            _ => anyhow::bail!("Email username and password must be set together"),
+        }
+
+        if config.to.is_empty() {
+            anyhow::bail!("Email notifier has no recipients");
+        }
+        let mailer = Arc::new(Mailer {
+            transport: transport.build(),
+            from: parse_mailbox(&config.from)?,
+            to: config
+                .to
+                .iter()
+                .map(|to| parse_mailbox(to))
+                .collect::<Result<_>>()?,
+            retries: config.retries,
+        });
+
+        let pending = Arc::new(Mutex::new(Digest::default()));
+        let digest = config.digest.map(|period| {
+            let mailer = mailer.clone();
+            let pending = pending.clone();
+            tokio::spawn(async move {
+                let mut ticker = time::interval_at(time::Instant::now() + period, period);
+                loop {
+                    ticker.tick().await;
+                    let digest = mem::take(&mut *pending.lock().unwrap());
+                    send_digest(&mailer, digest).await;
+                }
+            })
+        });
+
+        Ok(Self {
+            mailer,
+            pending,
+            digest,
+        })
+    }
+}
+
+// A reload replaces the notifier, send what the digest holds so far.
+impl Drop for Email {
+    fn drop(&mut self) {
+        if let Some(digest) = &self.digest {
+            digest.abort();
+            let digest = mem::take(&mut *self.pending.lock().unwrap());
+            let mailer = self.mailer.clone();
+            tokio::spawn(async move { send_digest(&mailer, digest).await });
+        }
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .with_context(|| format!("Invalid email address {:?}", address))
+}
+
+impl Mailer {
+    async fn send(&self, subject: &str, body: String) -> Result<()> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.body(body)?;
+
+        retry("Sending email", self.retries, || async {
+            self.transport.send(message.clone()).await?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+async fn send_digest(mailer: &Mailer, digest: Digest) {
+    let Digest { alerts, ranges } = digest;
+    if alerts.is_empty() {
+        return;
+    }
+
+    let pairs: BTreeSet<_> = alerts.iter().map(|alert| alert.pair.to_string()).collect();
+    let pairs: Vec<_> = pairs.into_iter().collect();
+    let subject = format!("{} price alerts for {}", alerts.len(), pairs.join(", "));
+    let mut body = String::new();
+    for (pair, (low, high)) in &ranges {
+        let _ = writeln!(body, "{} ranged {:.2} - {:.2}", pair, low, high);
+    }
+    for alert in &alerts {
+        let _ = write!(
+            body,
+            "\n{}  {} ({})",
+            alert.timestamp(),
+            alert.headline(),
+            alert.rule
+        );
+    }
+    body.push('\n');
+
+    match mailer.send(&subject, body).await {
+        Ok(()) => info!("Emailed a digest of {} alerts", alerts.len()),
+        Err(e) => error!("Failed to email alert digest: {:#}", e),
+    }
+}
+
+#[async_trait]
+impl Notifier for Email {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn price(&self, pair: &Pair, price: f64) {
+        if self.digest.is_some() {
+            self.pending.lock().unwrap().see(pair, price);
+        }
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        if self.digest.is_some() {
+            let mut pending = self.pending.lock().unwrap();
+            // The move may start from before the digest period.
+            pending.see(&alert.pair, alert.change.from);
+            pending.see(&alert.pair, alert.change.to);
+            pending.alerts.push(alert.clone());
+            return Ok(());
+        }
+
+        let mut body = String::new();
+        for (title, value) in alert.fields() {
+            let _ = writeln!(body, "{}: {}", title, value.trim_matches('`'));
+        }
+        let _ = writeln!(body, "Time: {}", alert.timestamp());
+        self.mailer.send(&alert.headline(), body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule::Move, sound::Sound};
+    use std::time::SystemTime;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    // Speaks just enough SMTP to accept messages, and passes on their data.
+    async fn smtp_sink() -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = socket.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    write.write_all(b"220 sink\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let reply: &[u8] = match line.split(' ').next().unwrap() {
+                            "EHLO" | "HELO" => b"250 sink\r\n",
+                            "DATA" => {
+                                write.write_all(b"354 go on\r\n").await.unwrap();
+                                let mut data = String::new();
+                                while let Ok(Some(line)) = lines.next_line().await {
+                                    if line == "." {
+                                        break;
+                                    }
+                                    data.push_str(&line);
+                                    data.push('\n');
+                                }
+                                tx.send(data).await.unwrap();
+                                b"250 queued\r\n"
+                            }
+                            "QUIT" => {
+                                let _ = write.write_all(b"221 bye\r\n").await;
+                                return;
+                            }
+                            _ => b"250 ok\r\n",
+                        };
+                        write.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        (port, rx)
+    }
+
+    fn config(port: u16, digest: Option<Duration>) -> EmailConfig {
+        EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "alerts@example.com".to_string(),
+            to: vec!["me@example.com".to_string()],
+            digest,
+            retries: 0,
+        }
+    }
+
+    fn alert(from: f64, to: f64) -> Alert {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(from, to),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::now(),
+        }
+    }
+
+    async fn next_message(messages: &mut mpsc::Receiver<String>) -> String {
+        time::timeout(Duration::from_secs(5), messages.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_each_alert() {
+        let (port, mut messages) = smtp_sink().await;
+        let email = Email::new(&config(port, None)).unwrap();
+        email.notify(&alert(100.0, 102.0)).await.unwrap();
+
+        let message = next_message(&mut messages).await;
+        assert!(message.contains("To: me@example.com"), "{}", message);
+        assert!(message.contains("Price: 102.00"), "{}", message);
+        assert!(message.contains("Rule: 1% change"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn digest_reports_the_range_seen_between_alerts() {
+        let (port, mut messages) = smtp_sink().await;
+        let email = Email::new(&config(port, Some(Duration::from_millis(300)))).unwrap();
+        let btc = Pair::new("BTC", "USD");
+        for price in [100.0, 96.5, 101.0] {
+            email.price(&btc, price);
+        }
+        email.price(&Pair::new("BTC", "EUR"), 93000.0);
+        email.notify(&alert(100.0, 101.0)).await.unwrap();
+        email.price(&btc, 104.25);
+        email.notify(&alert(104.25, 102.0)).await.unwrap();
+
+        let message = next_message(&mut messages).await;
+        assert!(
+            message.contains("BTC/USD ranged 96.50 - 104.25"),
+            "{}",
+            message
+        );
+        assert!(message.contains("(1% change)"), "{}", message);
+        // Only pairs that alerted are named in the subject.
+        assert!(
+            message.contains("Subject: 2 price alerts for BTC/USD\n"),
+            "{}",
+            message
+        );
+
+        // Nothing new, so no second digest.
+        assert!(time::timeout(Duration::from_millis(500), messages.recv())
+            .await
+            .is_err());
+    }
+}
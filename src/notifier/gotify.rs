@@ -0,0 +1,115 @@
+use super::{check_url, default_retries, json_headers, post, Alert, Notifier};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Client,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GotifyConfig {
+    /// The Gotify server, e.g. https://gotify.example.com
+    pub url: String,
+    /// An application token.
+    pub token: String,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+/// Pushes alerts to a Gotify server, from a silent priority 2 for small
+/// moves up to priority 10 for the biggest.
+pub struct Gotify {
+    client: Client,
+    config: GotifyConfig,
+    headers: HeaderMap,
+}
+
+impl Gotify {
+    pub fn new(client: Client, config: GotifyConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        let mut headers = json_headers();
+        let token = HeaderValue::try_from(&config.token).context("Invalid Gotify token")?;
+        headers.insert("X-Gotify-Key", token);
+        Ok(Self {
+            client,
+            config,
+            headers,
+        })
+    }
+}
+
+fn message(alert: &Alert) -> Value {
+    json!({
+        "title": alert.headline(),
+        "message": format!("{} ({})", alert.delta(), alert.rule),
+        "priority": alert.change.magnitude() * 2,
+    })
+}
+
+#[async_trait]
+impl Notifier for Gotify {
+    fn name(&self) -> &str {
+        "gotify"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = message(alert).to_string();
+        let url = format!("{}/message", self.config.url.trim_end_matches('/'));
+        post(&self.client, &url, &self.headers, body, self.config.retries).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound, testing};
+    use std::time::SystemTime;
+
+    fn alert(to: f64) -> Alert {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "0.5% change".to_string(),
+            change: Move::new(100.0, to),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn priority_follows_the_move() {
+        for (to, priority) in [
+            (100.4999, 2),
+            (100.5, 4),
+            (101.0, 6),
+            (98.0, 8),
+            (105.0, 10),
+        ] {
+            assert_eq!(message(&alert(to))["priority"], priority, "{}", to);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_the_token_in_a_header() {
+        let (url, mut requests) = testing::serve(&[(200, "{}")]).await;
+        let config = GotifyConfig {
+            url: format!("{}/", url),
+            token: "AbCdEf.123".to_string(),
+            retries: 0,
+        };
+        let gotify = Gotify::new(Client::new(), config).unwrap();
+        gotify.notify(&alert(101.0)).await.unwrap();
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/message")
+        );
+        assert_eq!(request.headers["x-gotify-key"], "AbCdEf.123");
+        let body: Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body, message(&alert(101.0)));
+    }
+}
@@ -0,0 +1,104 @@
+use super::{check_url, json_headers, post, Alert, ChatConfig, Notifier};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+/// Posts alerts to a Mattermost incoming webhook as message attachments.
+pub struct Mattermost {
+    client: Client,
+    config: ChatConfig,
+}
+
+impl Mattermost {
+    pub fn new(client: Client, config: ChatConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        Ok(Self { client, config })
+    }
+}
+
+fn message(alert: &Alert, config: &ChatConfig) -> Value {
+    let fields: Vec<_> = alert
+        .fields()
+        .into_iter()
+        .map(|(title, value)| json!({ "title": title, "value": value, "short": title != "Trend" }))
+        .collect();
+    let mut message = json!({
+        "attachments": [{
+            "fallback": alert.headline(),
+            "color": format!("#{:06x}", alert.color()),
+            "title": alert.headline(),
+            "fields": fields,
+            "footer": alert.timestamp(),
+        }],
+    });
+    if let Some(channel) = &config.channel {
+        message["channel"] = json!(channel);
+    }
+    if let Some(username) = &config.username {
+        message["username"] = json!(username);
+    }
+    message
+}
+
+#[async_trait]
+impl Notifier for Mattermost {
+    fn name(&self) -> &str {
+        "mattermost"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = message(alert, &self.config).to_string();
+        post(
+            &self.client,
+            &self.config.url,
+            &json_headers(),
+            body,
+            self.config.retries,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn formats_an_attachment() {
+        let alert = Alert {
+            pair: Pair::new("ETH", "EUR"),
+            rule: "above 2000".to_string(),
+            change: Move::new(1990.0, 2010.0),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        let config = ChatConfig {
+            url: "https://chat.example.com/hooks/xyz".to_string(),
+            username: None,
+            channel: Some("town-square".to_string()),
+            retries: 0,
+        };
+        assert_eq!(
+            message(&alert, &config),
+            json!({
+                "channel": "town-square",
+                "attachments": [{
+                    "fallback": "▲ ETH/EUR +1.01% to 2010.00",
+                    "color": "#2eb67d",
+                    "title": "▲ ETH/EUR +1.01% to 2010.00",
+                    "fields": [
+                        { "title": "Price", "value": "2010.00", "short": true },
+                        { "title": "Change", "value": "+20.00 (+1.01%)", "short": true },
+                        { "title": "Previous", "value": "1990.00", "short": true },
+                        { "title": "Rule", "value": "above 2000", "short": true },
+                    ],
+                    "footer": "2023-11-14T22:13:20Z",
+                }],
+            })
+        );
+    }
+}
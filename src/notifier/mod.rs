@@ -0,0 +1,437 @@
+use crate::{
+    monitor::CommandRequest,
+    pair::Pair,
+    rule::{Direction, Move},
+    sound::Sound,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::mpsc, time};
+
+mod desktop;
+mod discord;
+mod email;
+mod gotify;
+mod mattermost;
+mod mqtt;
+mod ntfy;
+mod pushover;
+mod slack;
+mod telegram;
+mod webhook;
+
+#[cfg(target_os = "linux")]
+pub use desktop::Desktop;
+pub use desktop::DesktopConfig;
+pub use discord::Discord;
+pub use email::{Email, EmailConfig};
+pub use gotify::{Gotify, GotifyConfig};
+pub use mattermost::Mattermost;
+pub use mqtt::{Mqtt, MqttConfig};
+pub use ntfy::{Ntfy, NtfyConfig};
+pub use pushover::{Pushover, PushoverConfig};
+pub use slack::Slack;
+pub use telegram::{Telegram, TelegramConfig};
+pub use webhook::{Webhook, WebhookConfig};
+
+const DEFAULT_RETRIES: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+const SPARK: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A rule that fired.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub pair: Pair,
+    pub rule: String,
+    pub change: Move,
+    /// The sound picked for this rule and direction.
+    pub sound: Sound,
+    /// Recent prices, oldest first.
+    pub trend: Vec<f64>,
+    pub time: SystemTime,
+}
+
+/// The JSON form of an [`Alert`].
+#[derive(Debug, Serialize)]
+pub struct Payload {
+    pub pair: String,
+    pub price: f64,
+    pub previous_price: f64,
+    pub change_percent: f64,
+    pub direction: Direction,
+    pub rule: String,
+    pub timestamp: String,
+}
+
+impl Alert {
+    pub fn payload(&self) -> Payload {
+        Payload {
+            pair: self.pair.to_string(),
+            price: self.change.to,
+            previous_price: self.change.from,
+            change_percent: self.change.change() * 100.0,
+            direction: self.change.direction,
+            rule: self.rule.clone(),
+            timestamp: self.timestamp(),
+        }
+    }
+
+    /// Replaces `{{field}}` placeholders for the [`Payload`] fields in
+    /// `template`, escaping the values for use inside JSON strings when
+    /// `json` is set.
+    pub fn render(&self, template: &str, json: bool) -> String {
+        let payload = self.payload();
+        [
+            ("pair", payload.pair),
+            ("price", format!("{:.2}", payload.price)),
+            ("previous_price", format!("{:.2}", payload.previous_price)),
+            ("change_percent", format!("{:+.2}", payload.change_percent)),
+            ("direction", self.direction().to_string()),
+            ("rule", payload.rule),
+            ("timestamp", payload.timestamp),
+        ]
+        .iter()
+        .fold(template.to_string(), |text, (field, value)| {
+            let value = if json {
+                let quoted = serde_json::Value::from(value.as_str()).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            } else {
+                value.clone()
+            };
+            text.replace(&format!("{{{{{}}}}}", field), &value)
+        })
+    }
+
+    pub fn timestamp(&self) -> String {
+        humantime::format_rfc3339_seconds(self.time).to_string()
+    }
+
+    pub fn direction(&self) -> &'static str {
+        match self.change.direction {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+
+    pub fn arrow(&self) -> &'static str {
+        match self.change.direction {
+            Direction::Up => "▲",
+            Direction::Down => "▼",
+        }
+    }
+
+    /// E.g. `▲ BTC/USD +1.00% to 101000.00`.
+    pub fn headline(&self) -> String {
+        format!(
+            "{} {} {:+.2}% to {:.2}",
+            self.arrow(),
+            self.pair,
+            self.change.change() * 100.0,
+            self.change.to
+        )
+    }
+
+    /// E.g. `+999.00 (+1.00%)`.
+    pub fn delta(&self) -> String {
+        format!(
+            "{:+.2} ({:+.2}%)",
+            self.change.to - self.change.from,
+            self.change.change() * 100.0
+        )
+    }
+
+    /// Green for a rise, red for a drop, as 0xRRGGBB.
+    pub fn color(&self) -> u32 {
+        match self.change.direction {
+            Direction::Up => 0x2eb67d,
+            Direction::Down => 0xe01e5a,
+        }
+    }
+
+    /// Titled values for chat messages: price, change, previous price, rule
+    /// and, when there is one, the trend.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("Price", format!("{:.2}", self.change.to)),
+            ("Change", self.delta()),
+            ("Previous", format!("{:.2}", self.change.from)),
+            ("Rule", self.rule.clone()),
+        ];
+        if self.trend.len() > 1 {
+            fields.push(("Trend", format!("`{}`", self.sparkline())));
+        }
+        fields
+    }
+
+    /// The trend as a line of block characters, e.g. `▁▂▄▇█`.
+    pub fn sparkline(&self) -> String {
+        let low = self.trend.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = self.trend.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        self.trend
+            .iter()
+            .map(|price| {
+                let level = if high > low {
+                    (price - low) / (high - low) * (SPARK.len() - 1) as f64
+                } else {
+                    0.0
+                };
+                SPARK[level.round() as usize]
+            })
+            .collect()
+    }
+}
+
+/// Somewhere alerts are delivered to.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Called with every price seen, for notifiers that publish prices as
+    /// well as alerts.
+    fn price(&self, _pair: &Pair, _price: f64) {}
+
+    async fn notify(&self, alert: &Alert) -> Result<()>;
+}
+
+/// POSTs `body` to `url`, retrying failures `retries` times.
+///
+/// Webhook and bot URLs often hold a secret, so errors and logs only name
+/// the host.
+async fn post(
+    client: &Client,
+    url: &str,
+    headers: &HeaderMap,
+    body: String,
+    retries: u32,
+) -> Result<()> {
+    retry(&format!("POST to {}", host(url)), retries, || async {
+        client
+            .post(url)
+            .headers(headers.clone())
+            .timeout(TIMEOUT)
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?;
+        Ok(())
+    })
+    .await
+}
+
+// The host of `url`, safe to log.
+fn host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "notifier".to_string())
+}
+
+/// Runs `attempt`, retrying failures `retries` times with backoff doubling
+/// from 1s.
+async fn retry<F, Fut>(what: &str, retries: u32, attempt: F) -> Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut backoff = Duration::from_secs(1);
+    for _ in 0..retries {
+        match attempt().await {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                warn!("{} failed, retrying in {:?}: {:#}", what, backoff, e);
+                time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+    attempt().await.with_context(|| format!("{} failed", what))
+}
+
+fn json_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers
+}
+
+fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+/// Passes on only the alerts for moves in `direction`.
+struct OnlyDirection {
+    direction: Direction,
+    inner: Box<dyn Notifier>,
+}
+
+#[async_trait]
+impl Notifier for OnlyDirection {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn price(&self, pair: &Pair, price: f64) {
+        self.inner.price(pair, price)
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        if alert.change.direction != self.direction {
+            return Ok(());
+        }
+        self.inner.notify(alert).await
+    }
+}
+
+/// Settings shared by the chat notifiers.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatConfig {
+    /// The incoming webhook URL.
+    pub url: String,
+    /// Name to post as, when the webhook allows overriding it.
+    pub username: Option<String>,
+    /// Channel to post to instead of the webhook's own, for Slack and
+    /// Mattermost.
+    pub channel: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+/// A `[[notifiers]]` entry of the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NotifierConfig {
+    /// Only notify about moves in this direction.
+    pub direction: Option<Direction>,
+    #[serde(flatten)]
+    pub kind: NotifierKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierKind {
+    Webhook(WebhookConfig),
+    Slack(ChatConfig),
+    Discord(ChatConfig),
+    Mattermost(ChatConfig),
+    Telegram(TelegramConfig),
+    Email(EmailConfig),
+    Ntfy(NtfyConfig),
+    Gotify(GotifyConfig),
+    Pushover(PushoverConfig),
+    Desktop(DesktopConfig),
+    Mqtt(MqttConfig),
+}
+
+impl NotifierConfig {
+    /// Builds the notifier. Notifiers that take commands pass them on to
+    /// `commands`.
+    pub fn build(
+        &self,
+        client: Client,
+        commands: &mpsc::Sender<CommandRequest>,
+    ) -> Result<Arc<dyn Notifier>> {
+        let notifier: Box<dyn Notifier> = match &self.kind {
+            NotifierKind::Webhook(config) => Box::new(Webhook::new(client, config)?),
+            NotifierKind::Slack(config) => Box::new(Slack::new(client, config.clone())?),
+            NotifierKind::Discord(config) => Box::new(Discord::new(client, config.clone())?),
+            NotifierKind::Mattermost(config) => Box::new(Mattermost::new(client, config.clone())?),
+            NotifierKind::Telegram(config) => {
+                Box::new(Telegram::new(client, config, commands.clone())?)
+            }
+            NotifierKind::Email(config) => Box::new(Email::new(config)?),
+            NotifierKind::Ntfy(config) => Box::new(Ntfy::new(client, config.clone())?),
+            NotifierKind::Gotify(config) => Box::new(Gotify::new(client, config.clone())?),
+            NotifierKind::Pushover(config) => Box::new(Pushover::new(client, config.clone())?),
+            #[cfg(target_os = "linux")]
+            NotifierKind::Desktop(config) => Box::new(Desktop::new(config, commands.clone())),
+            #[cfg(not(target_os = "linux"))]
+            NotifierKind::Desktop(_) => anyhow::bail!("Desktop notifications need Linux"),
+            NotifierKind::Mqtt(config) => Box::new(Mqtt::new(config)?),
+        };
+        Ok(match self.direction {
+            Some(direction) => Arc::new(OnlyDirection {
+                direction,
+                inner: notifier,
+            }),
+            None => Arc::from(notifier),
+        })
+    }
+}
+
+fn check_url(url: &str) -> Result<()> {
+    reqwest::Url::parse(url).with_context(|| format!("Invalid notifier url {:?}", url))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_label_leaves_out_secrets() {
+        assert_eq!(
+            host("https://api.telegram.org/bot123:SECRET/sendMessage"),
+            "api.telegram.org"
+        );
+        assert_eq!(
+            host("https://gotify.example.com/message?token=SECRET"),
+            "gotify.example.com"
+        );
+        assert_eq!(host("not a url"), "notifier");
+    }
+
+    #[test]
+    fn render_escapes_values_for_json() {
+        let alert = Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: r#"above "100k" \ soon"#.to_string(),
+            change: Move::new(100.0, 101.0),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::UNIX_EPOCH,
+        };
+        let template = r#"{"text": "{{pair}} {{rule}}"}"#;
+
+        let body: serde_json::Value = serde_json::from_str(&alert.render(template, true)).unwrap();
+        assert_eq!(body["text"], r#"BTC/USD above "100k" \ soon"#);
+        assert_eq!(
+            alert.render(template, false),
+            r#"{"text": "BTC/USD above "100k" \ soon"}"#
+        );
+    }
+
+    fn sparkline(trend: &[f64]) -> String {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(100.0, 101.0),
+            sound: Sound::default(),
+            trend: trend.to_vec(),
+            time: SystemTime::UNIX_EPOCH,
+        }
+        .sparkline()
+    }
+
+    #[test]
+    fn sparkline_spans_the_trend() {
+        assert_eq!(sparkline(&[100.0, 100.0, 100.0]), "▁▁▁");
+        assert_eq!(
+            sparkline(&[100.0, 101.0, 102.0, 103.0, 104.0, 105.0, 106.0, 107.0]),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(sparkline(&[107.0, 100.0, 107.0]), "█▁█");
+        assert_eq!(sparkline(&[100.0]), "▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
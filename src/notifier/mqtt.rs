@@ -0,0 +1,363 @@
+use super::{Alert, Notifier};
+use crate::pair::Pair;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::Url;
+use rumqttc::{AsyncClient, MqttOptions, QoS, TlsConfiguration, Transport};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use tokio::{task::JoinHandle, time};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Publishes waiting for the connection before new ones are dropped.
+const QUEUE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Broker URL, `mqtt://host:1883`, or `mqtts://host:8883` for TLS.
+    pub url: String,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// PEM CA certificate to trust for TLS, on top of the system ones.
+    pub ca: Option<PathBuf>,
+    /// Topic every price goes to, `{{pair}}` is replaced by the pair.
+    #[serde(default = "default_price_topic")]
+    pub price_topic: String,
+    /// Topic alerts go to, as the JSON [`Payload`](super::Payload).
+    #[serde(default = "default_alert_topic")]
+    pub alert_topic: String,
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    /// Keep the last price on the broker for new subscribers.
+    #[serde(default = "default_retain")]
+    pub retain: bool,
+}
+
+fn default_client_id() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+fn default_price_topic() -> String {
+    "subtle-alert/{{pair}}/price".to_string()
+}
+
+fn default_alert_topic() -> String {
+    "subtle-alert/{{pair}}/alert".to_string()
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+fn default_retain() -> bool {
+    true
+}
+
+/// Publishes every price and alert to an MQTT broker.
+pub struct Mqtt {
+    client: AsyncClient,
+    price_topic: String,
+    alert_topic: String,
+    qos: QoS,
+    retain: bool,
+    connection: JoinHandle<()>,
+}
+
+impl Mqtt {
+    pub fn new(config: &MqttConfig) -> Result<Self> {
+        let (host, port, tls) = broker(&config.url)?;
+        let mut options = MqttOptions::new(&config.client_id, host, port);
+        match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                options.set_credentials(username, password);
+            }
+            (None, None) => {}
+            _ => anyhow::bail!("MQTT username and password must be set together"),
+        }
+        if tls {
+            let tls = match &config.ca {
+                Some(path) => TlsConfiguration::SimpleNative {
+                    ca: fs::read(path)
+                        .with_context(|| format!("Failed to read CA {}", path.display()))?,
+                    client_auth: None,
+                },
+                None => TlsConfiguration::Native,
+            };
+            options.set_transport(Transport::tls_with_config(tls));
+        } else if config.ca.is_some() {
+            anyhow::bail!("MQTT ca is only used with an mqtts:// url");
+        }
+        let qos = qos(config.qos)?;
+
+        // The event loop does the actual sending, and reconnects when polled
+        // after an error.
+        let (client, mut events) = AsyncClient::new(options, QUEUE);
+        let broker = config.url.clone();
+        let connection = tokio::spawn(async move {
+            loop {
+                match events.poll().await {
+                    Ok(event) => debug!("MQTT {:?}", event),
+                    Err(e) => {
+                        warn!("MQTT connection to {} failed: {}", broker, e);
+                        time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            price_topic: config.price_topic.clone(),
+            alert_topic: config.alert_topic.clone(),
+            qos,
+            retain: config.retain,
+            connection,
+        })
+    }
+}
+
+// A reload replaces the notifier, drop the old connection.
+impl Drop for Mqtt {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+// The host, port and whether to use TLS for a broker URL.
+fn broker(url: &str) -> Result<(String, u16, bool)> {
+    let url = Url::parse(url).with_context(|| format!("Invalid MQTT url {:?}", url))?;
+    let host = url.host_str().context("MQTT url has no host")?;
+    let (port, tls) = match url.scheme() {
+        "mqtt" | "tcp" => (1883, false),
+        "mqtts" | "ssl" => (8883, true),
+        scheme => anyhow::bail!(
+            "Unsupported MQTT scheme {:?}, expected mqtt or mqtts",
+            scheme
+        ),
+    };
+    Ok((host.to_string(), url.port().unwrap_or(port), tls))
+}
+
+fn qos(level: u8) -> Result<QoS> {
+    rumqttc::qos(level)
+        .map_err(|_| anyhow::anyhow!("Invalid MQTT qos {}, expected 0, 1 or 2", level))
+}
+
+fn topic(template: &str, pair: &Pair) -> String {
+    template.replace("{{pair}}", &pair.to_string())
+}
+
+#[async_trait]
+impl Notifier for Mqtt {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    fn price(&self, pair: &Pair, price: f64) {
+        let payload = json!({
+            "pair": pair.to_string(),
+            "price": price,
+            "timestamp": humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        });
+        // Never wait on the broker here, a price is stale by the next one.
+        if let Err(e) = self.client.try_publish(
+            topic(&self.price_topic, pair),
+            self.qos,
+            self.retain,
+            payload.to_string(),
+        ) {
+            debug!("Dropped MQTT price for {}: {}", pair, e);
+        }
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let payload = serde_json::to_string(&alert.payload())?;
+        self.client
+            .try_publish(
+                topic(&self.alert_topic, &alert.pair),
+                self.qos,
+                false,
+                payload,
+            )
+            .context("MQTT queue is full")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rule::Move, sound::Sound};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    fn config(extra: &str) -> MqttConfig {
+        toml::from_str(extra).unwrap()
+    }
+
+    #[test]
+    fn maps_urls_to_brokers() {
+        let cases = [
+            ("mqtt://broker", ("broker", 1883, false)),
+            ("tcp://broker:1884", ("broker", 1884, false)),
+            ("mqtts://broker", ("broker", 8883, true)),
+            ("ssl://10.0.0.2:8884", ("10.0.0.2", 8884, true)),
+        ];
+        for (url, (host, port, tls)) in cases {
+            assert_eq!(
+                broker(url).unwrap(),
+                (host.to_string(), port, tls),
+                "{}",
+                url
+            );
+        }
+        for url in ["broker:1883", "http://broker", "mqtt://"] {
+            assert!(broker(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn maps_qos_levels() {
+        assert_eq!(qos(0).unwrap(), QoS::AtMostOnce);
+        assert_eq!(qos(1).unwrap(), QoS::AtLeastOnce);
+        assert_eq!(qos(2).unwrap(), QoS::ExactlyOnce);
+        assert!(qos(3).is_err());
+    }
+
+    #[test]
+    fn fills_in_the_pair() {
+        let pair = Pair::new("BTC", "EUR");
+        assert_eq!(
+            topic("subtle-alert/{{pair}}/price", &pair),
+            "subtle-alert/BTC/EUR/price"
+        );
+        assert_eq!(topic("prices", &pair), "prices");
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_settings() {
+        let cases = [
+            ("url = \"mqtt://broker\"\nqos = 3", "qos"),
+            ("url = \"mqtt://broker\"\nusername = \"me\"", "together"),
+            ("url = \"mqtt://broker\"\nca = \"ca.pem\"", "mqtts"),
+            (
+                "url = \"mqtts://broker\"\nca = \"/nonexistent/ca.pem\"",
+                "/nonexistent/ca.pem",
+            ),
+        ];
+        for (settings, error) in cases {
+            let e = Mqtt::new(&config(settings)).err().unwrap();
+            assert!(format!("{:#}", e).contains(error), "{}: {:#}", settings, e);
+        }
+        assert!(Mqtt::new(&config("url = \"mqtt://broker\"")).is_ok());
+    }
+
+    // Accepts one client and sends on the (topic, retain, payload) of every
+    // PUBLISH, acking as a QoS 1 broker would.
+    async fn broker_stub() -> (u16, mpsc::UnboundedReceiver<(String, bool, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            loop {
+                let Ok(header) = socket.read_u8().await else {
+                    return;
+                };
+                let mut length = 0;
+                for shift in (0..28).step_by(7) {
+                    let byte = socket.read_u8().await.unwrap();
+                    length |= usize::from(byte & 0x7f) << shift;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                let mut packet = vec![0; length];
+                socket.read_exact(&mut packet).await.unwrap();
+
+                let reply: Vec<u8> = match header >> 4 {
+                    // CONNECT
+                    1 => vec![0x20, 2, 0, 0],
+                    // PUBLISH
+                    3 => {
+                        let qos = (header >> 1) & 3;
+                        let topic_len = usize::from(u16::from_be_bytes([packet[0], packet[1]]));
+                        let topic = String::from_utf8(packet[2..2 + topic_len].to_vec()).unwrap();
+                        let mut rest = &packet[2 + topic_len..];
+                        let mut reply = Vec::new();
+                        if qos > 0 {
+                            reply = vec![0x40, 2, rest[0], rest[1]];
+                            rest = &rest[2..];
+                        }
+                        let payload = String::from_utf8(rest.to_vec()).unwrap();
+                        let _ = tx.send((topic, header & 1 == 1, payload));
+                        reply
+                    }
+                    // PINGREQ
+                    12 => vec![0xd0, 0],
+                    _ => Vec::new(),
+                };
+                socket.write_all(&reply).await.unwrap();
+            }
+        });
+        (port, rx)
+    }
+
+    async fn next(
+        published: &mut mpsc::UnboundedReceiver<(String, bool, String)>,
+    ) -> (String, bool, serde_json::Value) {
+        let (topic, retain, payload) = time::timeout(Duration::from_secs(5), published.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        (topic, retain, serde_json::from_str(&payload).unwrap())
+    }
+
+    #[tokio::test]
+    async fn retains_prices_but_not_alerts() {
+        let (port, mut published) = broker_stub().await;
+        let mqtt = Mqtt::new(&config(&format!("url = \"mqtt://127.0.0.1:{}\"", port))).unwrap();
+        let btc = Pair::new("BTC", "USD");
+        mqtt.price(&btc, 101000.0);
+        mqtt.notify(&Alert {
+            pair: btc,
+            rule: "1% change".to_string(),
+            change: Move::new(100000.0, 101000.0),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::now(),
+        })
+        .await
+        .unwrap();
+
+        let (topic, retain, price) = next(&mut published).await;
+        assert_eq!(
+            (topic.as_str(), retain),
+            ("subtle-alert/BTC/USD/price", true)
+        );
+        assert_eq!(
+            (&price["pair"], &price["price"]),
+            (&json!("BTC/USD"), &json!(101000.0))
+        );
+        assert!(price["timestamp"].is_string());
+
+        let (topic, retain, alert) = next(&mut published).await;
+        assert_eq!(
+            (topic.as_str(), retain),
+            ("subtle-alert/BTC/USD/alert", false)
+        );
+        assert_eq!(alert["rule"], "1% change");
+        assert_eq!(alert["direction"], "up");
+    }
+}
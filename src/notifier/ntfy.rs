@@ -0,0 +1,146 @@
+use super::{check_url, default_retries, json_headers, post, Alert, Notifier};
+use crate::rule::Direction;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    Client,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub const URL: &str = "https://ntfy.sh";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NtfyConfig {
+    #[serde(default = "default_url")]
+    pub url: String,
+    pub topic: String,
+    /// Access token for a protected topic.
+    pub token: Option<String>,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_url() -> String {
+    URL.to_string()
+}
+
+/// Publishes alerts to an ntfy topic, with ntfy's 1-5 priority following the
+/// size of the move.
+pub struct Ntfy {
+    client: Client,
+    config: NtfyConfig,
+    headers: HeaderMap,
+}
+
+impl Ntfy {
+    pub fn new(client: Client, config: NtfyConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        let mut headers = json_headers();
+        if let Some(token) = &config.token {
+            let value =
+                HeaderValue::try_from(format!("Bearer {}", token)).context("Invalid ntfy token")?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(Self {
+            client,
+            config,
+            headers,
+        })
+    }
+}
+
+fn message(alert: &Alert, config: &NtfyConfig) -> Value {
+    let tag = match alert.change.direction {
+        Direction::Up => "chart_with_upwards_trend",
+        Direction::Down => "chart_with_downwards_trend",
+    };
+    json!({
+        "topic": config.topic,
+        "title": alert.headline(),
+        "message": format!("{} ({})", alert.delta(), alert.rule),
+        "priority": alert.change.magnitude(),
+        "tags": [tag],
+    })
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    fn name(&self) -> &str {
+        "ntfy"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = message(alert, &self.config).to_string();
+        post(
+            &self.client,
+            &self.config.url,
+            &self.headers,
+            body,
+            self.config.retries,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound, testing};
+    use std::time::SystemTime;
+
+    fn alert(to: f64) -> Alert {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "0.5% change".to_string(),
+            change: Move::new(100.0, to),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    fn config(url: &str) -> NtfyConfig {
+        NtfyConfig {
+            url: url.to_string(),
+            topic: "prices".to_string(),
+            token: Some("tk_secret".to_string()),
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn priority_follows_the_move() {
+        let config = config(URL);
+        for (to, priority) in [(100.4999, 1), (100.5, 2), (101.0, 3), (98.0, 4), (105.0, 5)] {
+            assert_eq!(message(&alert(to), &config)["priority"], priority, "{}", to);
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_to_the_topic() {
+        let (url, mut requests) = testing::serve(&[(200, "{}")]).await;
+        let ntfy = Ntfy::new(Client::new(), config(&url)).unwrap();
+        ntfy.notify(&alert(99.0)).await.unwrap();
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/")
+        );
+        assert_eq!(request.headers["authorization"], "Bearer tk_secret");
+        let body: Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "topic": "prices",
+                "title": "▼ BTC/USD -1.00% to 99.00",
+                "message": "-1.00 (-1.00%) (0.5% change)",
+                "priority": 3,
+                "tags": ["chart_with_downwards_trend"],
+            })
+        );
+    }
+}
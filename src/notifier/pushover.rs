@@ -0,0 +1,104 @@
+use super::{check_url, default_retries, json_headers, post, Alert, Notifier};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+pub const API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushoverConfig {
+    /// The application's API token.
+    pub token: String,
+    /// The user or group key to notify.
+    pub user: String,
+    /// Only notify this device of the user.
+    pub device: Option<String>,
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_api_url() -> String {
+    API_URL.to_string()
+}
+
+/// Sends alerts through Pushover, quietly for small moves and as high
+/// priority for big ones.
+pub struct Pushover {
+    client: Client,
+    config: PushoverConfig,
+}
+
+impl Pushover {
+    pub fn new(client: Client, config: PushoverConfig) -> Result<Self> {
+        check_url(&config.api_url)?;
+        Ok(Self { client, config })
+    }
+}
+
+// Pushover's emergency priority 2 repeats until acknowledged, which is more
+// than a price alert calls for.
+fn priority(magnitude: u8) -> i8 {
+    match magnitude {
+        1 => -1,
+        2 | 3 => 0,
+        _ => 1,
+    }
+}
+
+#[async_trait]
+impl Notifier for Pushover {
+    fn name(&self) -> &str {
+        "pushover"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let mut body = json!({
+            "token": self.config.token,
+            "user": self.config.user,
+            "title": alert.headline(),
+            "message": format!("{} ({})", alert.delta(), alert.rule),
+            "priority": priority(alert.change.magnitude()),
+            "timestamp": alert.time.duration_since(std::time::UNIX_EPOCH)?.as_secs(),
+        });
+        if let Some(device) = &self.config.device {
+            body["device"] = json!(device);
+        }
+        post(
+            &self.client,
+            &self.config.api_url,
+            &json_headers(),
+            body.to_string(),
+            self.config.retries,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Move;
+
+    #[test]
+    fn priority_follows_the_move() {
+        for (to, expected) in [
+            (100.4999, -1),
+            (100.5, 0),
+            (101.0, 0),
+            (98.0, 1),
+            (105.0, 1),
+        ] {
+            assert_eq!(
+                priority(Move::new(100.0, to).magnitude()),
+                expected,
+                "{}",
+                to
+            );
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::{check_url, json_headers, post, Alert, ChatConfig, Notifier};
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+/// Posts alerts to a Slack incoming webhook as Block Kit messages.
+pub struct Slack {
+    client: Client,
+    config: ChatConfig,
+}
+
+impl Slack {
+    pub fn new(client: Client, config: ChatConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        Ok(Self { client, config })
+    }
+}
+
+fn message(alert: &Alert, config: &ChatConfig) -> Value {
+    let fields: Vec<_> = alert
+        .fields()
+        .into_iter()
+        .map(
+            |(title, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", title, value) }),
+        )
+        .collect();
+    let mut message = json!({
+        "text": alert.headline(),
+        "blocks": [
+            {
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("*{}*", alert.headline()) },
+            },
+            { "type": "section", "fields": fields },
+            {
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": alert.timestamp() }],
+            },
+        ],
+    });
+    if let Some(channel) = &config.channel {
+        message["channel"] = json!(channel);
+    }
+    if let Some(username) = &config.username {
+        message["username"] = json!(username);
+    }
+    message
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = message(alert, &self.config).to_string();
+        post(
+            &self.client,
+            &self.config.url,
+            &json_headers(),
+            body,
+            self.config.retries,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn formats_blocks() {
+        let alert = Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(100000.0, 101000.0),
+            sound: Sound::default(),
+            trend: vec![100000.0, 100500.0, 101000.0],
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        let config = ChatConfig {
+            url: "https://hooks.slack.com/services/T0/B0/X".to_string(),
+            username: Some("subtle-alert".to_string()),
+            channel: Some("#prices".to_string()),
+            retries: 0,
+        };
+        assert_eq!(
+            message(&alert, &config),
+            json!({
+                "text": "▲ BTC/USD +1.00% to 101000.00",
+                "channel": "#prices",
+                "username": "subtle-alert",
+                "blocks": [
+                    {
+                        "type": "section",
+                        "text": { "type": "mrkdwn", "text": "*▲ BTC/USD +1.00% to 101000.00*" },
+                    },
+                    {
+                        "type": "section",
+                        "fields": [
+                            { "type": "mrkdwn", "text": "*Price*\n101000.00" },
+                            { "type": "mrkdwn", "text": "*Change*\n+1000.00 (+1.00%)" },
+                            { "type": "mrkdwn", "text": "*Previous*\n100000.00" },
+                            { "type": "mrkdwn", "text": "*Rule*\n1% change" },
+                            { "type": "mrkdwn", "text": "*Trend*\n`▁▅█`" },
+                        ],
+                    },
+                    {
+                        "type": "context",
+                        "elements": [{ "type": "mrkdwn", "text": "2023-11-14T22:13:20Z" }],
+                    },
+                ],
+            })
+        );
+    }
+}
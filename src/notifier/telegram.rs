@@ -0,0 +1,360 @@
+use super::{check_url, default_retries, json_headers, post, Alert, Notifier};
+use crate::{
+    cli::parse_threshold,
+    monitor::{Command, CommandRequest},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::{info, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time,
+};
+
+pub const API_URL: &str = "https://api.telegram.org";
+
+// getUpdates long-polls for this long before returning an empty batch.
+const POLL_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_RETRY: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "Commands:
+/price - last price of every pair
+/threshold 1.5 [PAIR] - alert on 1.5% changes
+/mute 30m - silence alerts, /mute off to undo
+/status - what is being watched";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Bot token from @BotFather.
+    pub token: String,
+    /// Chat alerts go to. Commands are only taken from this chat.
+    pub chat_id: i64,
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// Answer commands sent to the bot.
+    #[serde(default = "default_commands")]
+    pub commands: bool,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_api_url() -> String {
+    API_URL.to_string()
+}
+
+fn default_commands() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Sends alerts through a Telegram bot and answers its commands.
+pub struct Telegram {
+    bot: Bot,
+    retries: u32,
+    poller: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone)]
+struct Bot {
+    client: Client,
+    // API base URL with the token, e.g. https://api.telegram.org/bot123:ABC
+    url: String,
+    chat_id: i64,
+}
+
+impl Telegram {
+    /// Starts polling for commands, which are passed on to `commands`, unless
+    /// they are turned off.
+    pub fn new(
+        client: Client,
+        config: &TelegramConfig,
+        commands: mpsc::Sender<CommandRequest>,
+    ) -> Result<Self> {
+        check_url(&config.api_url)?;
+        let bot = Bot {
+            client,
+            url: format!(
+                "{}/bot{}",
+                config.api_url.trim_end_matches('/'),
+                config.token
+            ),
+            chat_id: config.chat_id,
+        };
+        let poller = config.commands.then(|| {
+            let bot = bot.clone();
+            tokio::spawn(async move { bot.poll(commands).await })
+        });
+
+        Ok(Self {
+            bot,
+            retries: config.retries,
+            poller,
+        })
+    }
+}
+
+// A reload replaces the notifier, stop polling so only the new one does.
+impl Drop for Telegram {
+    fn drop(&mut self) {
+        if let Some(poller) = &self.poller {
+            poller.abort();
+        }
+    }
+}
+
+impl Bot {
+    async fn send(&self, text: &str, retries: u32) -> Result<()> {
+        let body = json!({ "chat_id": self.chat_id, "text": text }).to_string();
+        let url = format!("{}/sendMessage", self.url);
+        post(&self.client, &url, &json_headers(), body, retries).await
+    }
+
+    async fn updates(&self, offset: i64) -> Result<Vec<Update>> {
+        // The URL holds the bot token, keep it out of errors.
+        let response: TelegramResponse<Vec<Update>> = self
+            .client
+            .get(format!("{}/getUpdates", self.url))
+            .query(&[
+                ("offset", offset.to_string()),
+                ("timeout", POLL_TIMEOUT.as_secs().to_string()),
+            ])
+            .timeout(POLL_TIMEOUT * 2)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+        if !response.ok {
+            anyhow::bail!(
+                "Telegram error: {}",
+                response.description.unwrap_or_default()
+            );
+        }
+        response.result.context("Telegram sent no updates")
+    }
+
+    async fn poll(&self, commands: mpsc::Sender<CommandRequest>) {
+        let mut offset = 0;
+        loop {
+            let updates = match self.updates(offset).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    warn!("Failed to get Telegram updates: {:#}", e);
+                    time::sleep(POLL_RETRY).await;
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = update.update_id + 1;
+                let Some(Message {
+                    chat,
+                    text: Some(text),
+                }) = update.message
+                else {
+                    continue;
+                };
+                if chat.id != self.chat_id {
+                    warn!("Ignoring Telegram message from chat {}", chat.id);
+                    continue;
+                }
+
+                let reply = match parse_command(&text) {
+                    Some(Ok(command)) => {
+                        info!("Telegram command {:?}", text);
+                        let (reply_tx, reply) = oneshot::channel();
+                        if commands.send((command, reply_tx)).await.is_err() {
+                            return;
+                        }
+                        reply.await.unwrap_or_default()
+                    }
+                    Some(Err(usage)) => usage,
+                    None => continue,
+                };
+                if let Err(e) = self.send(&reply, 0).await {
+                    warn!("Failed to answer Telegram command: {:#}", e);
+                }
+            }
+        }
+    }
+}
+
+// None for a message that is not a command, the usage or an error to reply
+// with for a command that cannot be run.
+fn parse_command(text: &str) -> Option<std::result::Result<Command, String>> {
+    let mut words = text.split_whitespace();
+    let name = words.next()?.strip_prefix('/')?;
+    // Commands in groups may name the bot, e.g. /price@subtle_alert_bot.
+    let name = name.split('@').next().unwrap_or_default();
+    let args: Vec<_> = words.collect();
+
+    let command = match (name, args.as_slice()) {
+        ("price", []) => Ok(Command::Price),
+        ("status", []) => Ok(Command::Status),
+        ("threshold", [threshold, rest @ ..]) if rest.len() <= 1 => {
+            // A bare number is a percentage here, unlike on the command line.
+            let threshold = parse_threshold(&format!("{}%", threshold.trim_end_matches('%')))
+                .map_err(|e| format!("Invalid threshold: {}", e));
+            let pair = rest
+                .first()
+                .map(|pair| pair.parse())
+                .transpose()
+                .map_err(|e| format!("Invalid pair: {}", e));
+            threshold.and_then(|threshold| {
+                Ok(Command::Threshold {
+                    threshold,
+                    pair: pair?,
+                })
+            })
+        }
+        ("mute", ["off"]) | ("unmute", []) => Ok(Command::Mute(None)),
+        ("mute", [duration]) => humantime::parse_duration(duration)
+            .map(|duration| Command::Mute(Some(duration)))
+            .map_err(|e| format!("Invalid duration: {}", e)),
+        _ => Err(USAGE.to_string()),
+    };
+    Some(command)
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let mut text = alert.headline();
+        for (title, value) in alert.fields() {
+            text.push_str(&format!("\n{}: {}", title, value.trim_matches('`')));
+        }
+        self.bot.send(&text, self.retries).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, testing};
+
+    #[test]
+    fn parses_commands() {
+        let cases = [
+            ("/price", Ok(Command::Price)),
+            ("/price@subtle_alert_bot", Ok(Command::Price)),
+            ("/status", Ok(Command::Status)),
+            (
+                "/threshold 1.5",
+                Ok(Command::Threshold {
+                    threshold: 0.015,
+                    pair: None,
+                }),
+            ),
+            (
+                "/threshold 2% BTC/EUR",
+                Ok(Command::Threshold {
+                    threshold: 0.02,
+                    pair: Some(Pair::new("BTC", "EUR")),
+                }),
+            ),
+            (
+                "/mute 30m",
+                Ok(Command::Mute(Some(Duration::from_secs(30 * 60)))),
+            ),
+            ("/mute off", Ok(Command::Mute(None))),
+            ("/unmute", Ok(Command::Mute(None))),
+        ];
+        for (text, command) in cases {
+            assert_eq!(parse_command(text), Some(command), "{}", text);
+        }
+    }
+
+    #[test]
+    fn answers_bad_commands_with_help() {
+        for text in ["/help", "/price now", "/threshold", "/mute", "/start"] {
+            assert_eq!(
+                parse_command(text),
+                Some(Err(USAGE.to_string())),
+                "{}",
+                text
+            );
+        }
+        for text in ["/threshold 150", "/threshold 1 USD", "/mute soon"] {
+            assert!(
+                matches!(parse_command(text), Some(Err(e)) if e != USAGE),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse_command("hello"), None);
+        assert_eq!(parse_command(""), None);
+    }
+
+    #[tokio::test]
+    async fn takes_commands_only_from_the_configured_chat() {
+        let updates = r#"{"ok":true,"result":[
+            {"update_id":7,"message":{"message_id":1,"chat":{"id":42,"type":"private"},"text":"/mute 30m"}},
+            {"update_id":8,"message":{"message_id":2,"chat":{"id":666,"type":"private"},"text":"/mute 1d"}}
+        ]}"#;
+        let (url, mut requests) =
+            testing::serve(&[(200, updates), (200, r#"{"ok":true,"result":{}}"#)]).await;
+        let config = TelegramConfig {
+            token: "123:ABC".to_string(),
+            chat_id: 42,
+            api_url: url,
+            commands: true,
+            retries: 0,
+        };
+        let (commands_tx, mut commands) = mpsc::channel(4);
+        let _telegram = Telegram::new(Client::new(), &config, commands_tx).unwrap();
+
+        let (command, reply) = commands.recv().await.unwrap();
+        assert_eq!(command, Command::Mute(Some(Duration::from_secs(30 * 60))));
+        reply.send("Muted for 30m".to_string()).unwrap();
+
+        let poll = requests.recv().await.unwrap();
+        assert_eq!(poll.path, "/bot123:ABC/getUpdates?offset=0&timeout=30");
+        let answer = requests.recv().await.unwrap();
+        assert_eq!(
+            (answer.method.as_str(), answer.path.as_str()),
+            ("POST", "/bot123:ABC/sendMessage")
+        );
+        let body: serde_json::Value = serde_json::from_str(&answer.body).unwrap();
+        assert_eq!(body, json!({ "chat_id": 42, "text": "Muted for 30m" }));
+
+        // The next poll moves past both updates, and the other chat's
+        // message never became a command.
+        let poll = requests.recv().await.unwrap();
+        assert_eq!(poll.path, "/bot123:ABC/getUpdates?offset=9&timeout=30");
+        assert!(commands.try_recv().is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use super::{check_url, default_retries, json_headers, post, Alert, Notifier};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Client,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Retries after a failed delivery, with doubling backoff from 1s.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Body template with `{{field}}` placeholders, instead of the JSON
+    /// payload.
+    pub body: Option<String>,
+    /// Content type of the templated body, JSON by default. Values filled
+    /// into a JSON body are escaped.
+    pub content_type: Option<String>,
+}
+
+/// POSTs every alert to a URL, as the JSON [`Payload`](super::Payload) or
+/// a templated body.
+pub struct Webhook {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+    retries: u32,
+    body: Option<String>,
+    // Whether the body is sent as JSON, so templated values need escaping.
+    json: bool,
+}
+
+impl Webhook {
+    pub fn new(client: Client, config: &WebhookConfig) -> Result<Self> {
+        check_url(&config.url)?;
+        let mut headers = json_headers();
+        if let Some(content_type) = &config.content_type {
+            let value = HeaderValue::try_from(content_type.as_str())
+                .with_context(|| format!("Invalid webhook content type {:?}", content_type))?;
+            headers.insert(CONTENT_TYPE, value);
+        }
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name.as_str())
+                .with_context(|| format!("Invalid webhook header name {:?}", name))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .with_context(|| format!("Invalid value for webhook header {}", name))?;
+            headers.insert(name, value);
+        }
+        let json = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            headers,
+            retries: config.retries,
+            body: config.body.clone(),
+            json,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<()> {
+        let body = match &self.body {
+            Some(template) => alert.render(template, self.json),
+            None => serde_json::to_string(&alert.payload())?,
+        };
+        post(&self.client, &self.url, &self.headers, body, self.retries).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pair::Pair, rule::Move, sound::Sound, testing};
+    use std::time::{Duration, SystemTime};
+
+    fn alert() -> Alert {
+        Alert {
+            pair: Pair::new("BTC", "USD"),
+            rule: "1% change".to_string(),
+            change: Move::new(100000.0, 101000.0),
+            sound: Sound::default(),
+            trend: Vec::new(),
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+
+    fn build(config: &str) -> Webhook {
+        let config: WebhookConfig = toml::from_str(config).unwrap();
+        Webhook::new(Client::new(), &config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn posts_the_payload_with_custom_headers() {
+        let (url, mut requests) = testing::serve(&[(200, "")]).await;
+        let webhook = build(&format!(
+            r#"
+            url = "{}/hook"
+            headers = {{ Authorization = "Bearer secret" }}
+            "#,
+            url
+        ));
+        webhook.notify(&alert()).await.unwrap();
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/hook")
+        );
+        assert_eq!(request.headers["authorization"], "Bearer secret");
+        assert_eq!(request.headers["content-type"], "application/json");
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "pair": "BTC/USD",
+                "price": 101000.0,
+                "previous_price": 100000.0,
+                "change_percent": 1.0,
+                "direction": "up",
+                "rule": "1% change",
+                "timestamp": "2023-11-14T22:13:20Z",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn fills_in_the_body_template() {
+        let (url, mut requests) = testing::serve(&[(200, "")]).await;
+        let webhook = build(&format!(
+            r#"
+            url = "{}"
+            body = '{{"text": "{{{{pair}}}} {{{{change_percent}}}}% to {{{{price}}}}"}}'
+            "#,
+            url
+        ));
+        webhook.notify(&alert()).await.unwrap();
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.body, r#"{"text": "BTC/USD +1.00% to 101000.00"}"#);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries() {
+        let (url, mut requests) = testing::serve(&[(500, ""), (200, "")]).await;
+        let webhook = build(&format!("url = \"{}\"\nretries = 1", url));
+        webhook.notify(&alert()).await.unwrap();
+        assert!(requests.recv().await.is_some());
+        assert!(requests.recv().await.is_some());
+
+        // Without retries the first failure is final.
+        let (url, _requests) = testing::serve(&[(500, ""), (200, "")]).await;
+        let webhook = build(&format!("url = \"{}\"\nretries = 0", url));
+        assert!(webhook.notify(&alert()).await.is_err());
+    }
+}
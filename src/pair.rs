@@ -0,0 +1,113 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer};
+use std::{fmt, str::FromStr};
+
+// Used to split pairs written without a separator, e.g. `XBTEUR`. Longer
+// symbols come first so `USDT` wins over `USD`.
+const QUOTE_CURRENCIES: [&str; 13] = [
+    "USDT", "USDC", "DAI", "USD", "EUR", "GBP", "JPY", "CAD", "CHF", "AUD", "BTC", "XBT", "ETH",
+];
+
+/// A trading pair such as BTC/USD, using common (not Kraken) asset symbols.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pair {
+    pub base: String,
+    pub quote: String,
+}
+
+impl Pair {
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self {
+            base: normalize(base),
+            quote: normalize(quote),
+        }
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+impl FromStr for Pair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_uppercase();
+        if let Some((base, quote)) = s.split_once(['/', '-', '_']) {
+            if !base.is_empty() && !quote.is_empty() {
+                return Ok(Pair::new(base, quote));
+            }
+        } else if let Some((base, quote)) = split_legacy(&s) {
+            return Ok(Pair::new(base, quote));
+        } else if let Some(quote) = QUOTE_CURRENCIES
+            .iter()
+            .find(|quote| s.len() > quote.len() && s.ends_with(*quote))
+        {
+            return Ok(Pair::new(&s[..s.len() - quote.len()], quote));
+        }
+
+        anyhow::bail!(
+            "Invalid trading pair {:?}, expected e.g. BTC/USD or XBTEUR",
+            s
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Pair {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// Kraken's canonical names for older pairs glue two X/Z prefixed asset codes
+// together, e.g. XXBTZUSD or XETHXXBT.
+fn split_legacy(s: &str) -> Option<(&str, &str)> {
+    let bytes = s.as_bytes();
+    let is_prefix = |b: u8| b == b'X' || b == b'Z';
+    (s.len() == 8 && s.is_ascii() && is_prefix(bytes[0]) && is_prefix(bytes[4]))
+        .then(|| (&s[1..4], &s[5..8]))
+}
+
+// Maps Kraken's legacy asset codes to the symbols everyone else uses.
+fn normalize(symbol: &str) -> String {
+    let symbol = symbol.to_ascii_uppercase();
+    match symbol.as_str() {
+        "XBT" => "BTC".to_string(),
+        "XDG" => "DOGE".to_string(),
+        _ => symbol,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pairs() {
+        let cases = [
+            ("BTC/EUR", ("BTC", "EUR")),
+            ("btc-eur", ("BTC", "EUR")),
+            (" eth_usd ", ("ETH", "USD")),
+            ("XBTEUR", ("BTC", "EUR")),
+            ("XXBTZUSD", ("BTC", "USD")),
+            ("XETHXXBT", ("ETH", "BTC")),
+            ("BTCUSDT", ("BTC", "USDT")),
+            ("BTCUSD", ("BTC", "USD")),
+            ("XDG/USD", ("DOGE", "USD")),
+        ];
+        for (s, (base, quote)) in cases {
+            assert_eq!(s.parse::<Pair>().unwrap(), Pair::new(base, quote), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_pairs() {
+        for s in ["USD", "/USD", "BTC/", "", "BTCXYZ"] {
+            assert!(s.parse::<Pair>().is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,587 @@
+use crate::{history::PriceHistory, sound::Sound};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn of(from: f64, to: f64) -> Self {
+        if to >= from {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+}
+
+/// A price move that met a rule: from the reference price the rule compares
+/// against (previous interval, window open/high/low) to the current price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Move {
+    pub direction: Direction,
+    pub from: f64,
+    pub to: f64,
+}
+
+impl Move {
+    pub fn new(from: f64, to: f64) -> Self {
+        Self {
+            direction: Direction::of(from, to),
+            from,
+            to,
+        }
+    }
+
+    /// Signed change as a fraction of `from`.
+    pub fn change(&self) -> f64 {
+        (self.to - self.from) / self.from
+    }
+
+    /// How big the move is from 1 to 5: under 0.5%, then from 0.5%, 1%, 2%
+    /// and 5%.
+    pub fn magnitude(&self) -> u8 {
+        let size = self.change().abs();
+        1 + [0.005, 0.01, 0.02, 0.05]
+            .iter()
+            .filter(|&&step| size >= step)
+            .count() as u8
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:+.2}% ({:.2} -> {:.2})",
+            self.change() * 100.0,
+            self.from,
+            self.to
+        )
+    }
+}
+
+/// Which price of a window a [`RuleKind::Window`] move is measured from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowReference {
+    /// The first price in the window.
+    Open,
+    /// The window's high, so only drops count.
+    High,
+    /// The window's low, so only rises count.
+    Low,
+    /// Drops from the high and rises from the low.
+    #[default]
+    HighLow,
+}
+
+/// The price levels a [`RuleKind::Level`] rule watches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Levels {
+    Fixed(f64),
+    /// Every multiple of the step, e.g. each round $1,000.
+    Every(f64),
+}
+
+impl Levels {
+    // The furthest level passed moving from `from` to `to`, if any.
+    fn crossed(self, from: f64, to: f64) -> Option<f64> {
+        let level = match self {
+            Levels::Fixed(level) => level,
+            Levels::Every(step) if to > from => (to / step).floor() * step,
+            Levels::Every(step) => (to / step).ceil() * step,
+        };
+        let crossed = if to > from {
+            from < level && level <= to
+        } else {
+            to <= level && level < from
+        };
+        crossed.then_some(level)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleKind {
+    /// The price moved at least `threshold` (as a fraction) since the
+    /// previous interval.
+    Change { threshold: f64 },
+    /// The price moved at least `threshold` within the last `window`.
+    Window {
+        threshold: f64,
+        window: Duration,
+        from: WindowReference,
+    },
+    /// The price crossed one of `levels`, in `direction` only when set.
+    ///
+    /// After firing at a level the rule ignores that level until the price
+    /// has moved `hysteresis` (as a fraction of the level) away from it, so
+    /// trading around the level does not alert on every tick.
+    Level {
+        levels: Levels,
+        direction: Option<Direction>,
+        hysteresis: f64,
+    },
+}
+
+impl RuleKind {
+    /// How much history this rule needs to look back on.
+    pub fn lookback(&self) -> Duration {
+        match self {
+            RuleKind::Change { .. } | RuleKind::Level { .. } => Duration::ZERO,
+            RuleKind::Window { window, .. } => *window,
+        }
+    }
+
+    /// The cooldown used when none is configured for the rule.
+    pub fn default_cooldown(&self, cooldown: Duration) -> Duration {
+        match self {
+            RuleKind::Change { .. } => cooldown,
+            // A window rule keeps matching for as long as the move stays in
+            // the window, so it is quiet for that long.
+            RuleKind::Window { window, .. } => cooldown.max(*window),
+            // Hysteresis already keeps level rules from repeating, and a new
+            // level should not wait on the previous one.
+            RuleKind::Level { .. } => Duration::ZERO,
+        }
+    }
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleKind::Change { threshold } => write!(f, "{} change", percent(*threshold)),
+            RuleKind::Window {
+                threshold, window, ..
+            } => write!(
+                f,
+                "{} within {}",
+                percent(*threshold),
+                humantime::format_duration(*window)
+            ),
+            RuleKind::Level {
+                levels, direction, ..
+            } => {
+                let side = match direction {
+                    Some(Direction::Up) => "above",
+                    Some(Direction::Down) => "below",
+                    None => "crossing",
+                };
+                match levels {
+                    Levels::Fixed(level) => write!(f, "{} {}", side, level),
+                    Levels::Every(step) => write!(f, "{} every {}", side, step),
+                }
+            }
+        }
+    }
+}
+
+// Rounds away float noise such as 7.000000000000001%.
+fn percent(fraction: f64) -> String {
+    format!("{}%", (fraction * 1e6).round() / 1e4)
+}
+
+/// An alert condition on one pair, with its own sound and cooldown.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub kind: RuleKind,
+    pub sound_up: Sound,
+    pub sound_down: Sound,
+    pub cooldown: Cooldown,
+    // Last level a level rule fired at, and whether it may fire there again.
+    anchor: Option<(f64, bool)>,
+}
+
+impl Rule {
+    pub fn new(
+        id: String,
+        kind: RuleKind,
+        sound_up: Sound,
+        sound_down: Sound,
+        cooldown: Cooldown,
+    ) -> Self {
+        Self {
+            id,
+            kind,
+            sound_up,
+            sound_down,
+            cooldown,
+            anchor: None,
+        }
+    }
+
+    /// Keeps the state of `old` when a rule is reconfigured.
+    pub fn inherit(&mut self, old: &Rule) {
+        self.cooldown.inherit(&old.cooldown);
+        if self.kind == old.kind {
+            self.anchor = old.anchor;
+        }
+    }
+
+    pub fn sound(&self, direction: Direction) -> &Sound {
+        match direction {
+            Direction::Up => &self.sound_up,
+            Direction::Down => &self.sound_down,
+        }
+    }
+
+    /// Returns the move that met the rule, if any.
+    ///
+    /// Change rules compare against `reference`, the price at the previous
+    /// interval, so a move spread over many streamed trades still counts.
+    /// Level rules look for a crossing since `last_price`, the previous quote.
+    pub fn should_alert(
+        &mut self,
+        reference: Option<f64>,
+        last_price: Option<f64>,
+        current_price: f64,
+        history: &PriceHistory,
+        now: Instant,
+    ) -> Option<Move> {
+        match self.kind {
+            RuleKind::Change { threshold } => {
+                let change = Move::new(reference?, current_price);
+                (change.change().abs() >= threshold).then_some(change)
+            }
+            RuleKind::Window {
+                threshold,
+                window,
+                from,
+            } => {
+                let stats = history.window(now, window)?;
+                let rise = Move::new(stats.low, current_price);
+                let drop = Move::new(stats.high, current_price);
+                let change = match from {
+                    WindowReference::Open => Move::new(stats.open, current_price),
+                    WindowReference::High => drop,
+                    WindowReference::Low => rise,
+                    WindowReference::HighLow if rise.change() >= -drop.change() => rise,
+                    WindowReference::HighLow => drop,
+                };
+                (change.change().abs() >= threshold).then_some(change)
+            }
+            RuleKind::Level {
+                levels,
+                direction,
+                hysteresis,
+            } => {
+                if let Some((level, armed)) = &mut self.anchor {
+                    *armed |= (current_price - *level).abs() >= *level * hysteresis;
+                }
+
+                let last_price = last_price?;
+                let level = levels.crossed(last_price, current_price)?;
+                let crossing = Move::new(last_price, current_price);
+                if direction.is_some_and(|d| d != crossing.direction)
+                    || self.anchor == Some((level, false))
+                {
+                    return None;
+                }
+
+                self.anchor = Some((level, false));
+                Some(crossing)
+            }
+        }
+    }
+}
+
+/// Limits how often a rule alerts, separately for up and down moves.
+///
+/// With `escalate` set, an alert still fires during the cooldown once the
+/// price has moved that much further (as a fraction) in the same direction
+/// since the previous alert, so a continuing crash is not silenced.
+#[derive(Debug, Clone)]
+pub struct Cooldown {
+    pub period: Duration,
+    pub escalate: Option<f64>,
+    last_up: Option<(Instant, f64)>,
+    last_down: Option<(Instant, f64)>,
+}
+
+impl Cooldown {
+    pub fn new(period: Duration, escalate: Option<f64>) -> Self {
+        Self {
+            period,
+            escalate,
+            last_up: None,
+            last_down: None,
+        }
+    }
+
+    pub fn allows(&self, direction: Direction, price: f64, now: Instant) -> bool {
+        let Some((at, alert_price)) = self.last(direction) else {
+            return true;
+        };
+        if now.duration_since(at) >= self.period {
+            return true;
+        }
+
+        let further = match direction {
+            Direction::Up => (price - alert_price) / alert_price,
+            Direction::Down => (alert_price - price) / alert_price,
+        };
+        self.escalate.is_some_and(|step| further >= step)
+    }
+
+    pub fn record(&mut self, direction: Direction, price: f64, now: Instant) {
+        let last = Some((now, price));
+        match direction {
+            Direction::Up => self.last_up = last,
+            Direction::Down => self.last_down = last,
+        }
+    }
+
+    /// Keeps the alert history of `old` when a rule is reconfigured.
+    pub fn inherit(&mut self, old: &Cooldown) {
+        self.last_up = old.last_up;
+        self.last_down = old.last_down;
+    }
+
+    fn last(&self, direction: Direction) -> Option<(Instant, f64)> {
+        match direction {
+            Direction::Up => self.last_up,
+            Direction::Down => self.last_down,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(kind: RuleKind, cooldown: Cooldown) -> Rule {
+        Rule::new(
+            kind.to_string(),
+            kind,
+            Sound::default(),
+            Sound::default(),
+            cooldown,
+        )
+    }
+
+    fn level(levels: Levels, direction: Option<Direction>, hysteresis: f64) -> Rule {
+        let kind = RuleKind::Level {
+            levels,
+            direction,
+            hysteresis,
+        };
+        rule(kind, Cooldown::new(Duration::ZERO, None))
+    }
+
+    // Feeds `prices` to a level rule one quote after another, returning the
+    // moves it fired on.
+    fn crossings(rule: &mut Rule, prices: &[f64]) -> Vec<(f64, f64)> {
+        let history = PriceHistory::new(Duration::ZERO);
+        let now = Instant::now();
+        prices
+            .windows(2)
+            .filter_map(|pair| {
+                rule.should_alert(None, Some(pair[0]), pair[1], &history, now)
+                    .map(|change| (change.from, change.to))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn level_fires_on_an_exact_touch_once() {
+        let mut rule = level(Levels::Fixed(100.0), None, 0.001);
+        assert_eq!(
+            crossings(&mut rule, &[99.0, 100.0, 101.0]),
+            vec![(99.0, 100.0)]
+        );
+    }
+
+    #[test]
+    fn every_fires_once_for_the_furthest_level_of_a_jump() {
+        assert_eq!(
+            Levels::Every(1000.0).crossed(99_500.0, 102_300.0),
+            Some(102_000.0)
+        );
+        assert_eq!(
+            Levels::Every(1000.0).crossed(102_300.0, 99_500.0),
+            Some(100_000.0)
+        );
+        assert_eq!(Levels::Every(1000.0).crossed(101_100.0, 101_900.0), None);
+
+        let mut rule = level(Levels::Every(1000.0), None, 0.001);
+        assert_eq!(
+            crossings(&mut rule, &[99_500.0, 102_300.0]),
+            vec![(99_500.0, 102_300.0)]
+        );
+    }
+
+    #[test]
+    fn level_rearms_only_after_hysteresis() {
+        let mut rule = level(Levels::Fixed(100.0), None, 0.01);
+        // Back and forth within 1% of the level, then away and back.
+        assert_eq!(
+            crossings(&mut rule, &[99.0, 101.0, 99.5, 100.5, 101.2, 99.8]),
+            vec![(99.0, 101.0), (101.2, 99.8)]
+        );
+    }
+
+    #[test]
+    fn above_and_below_ignore_the_other_direction() {
+        let mut above = level(Levels::Fixed(100.0), Some(Direction::Up), 0.001);
+        assert_eq!(
+            crossings(&mut above, &[101.0, 99.0, 101.0]),
+            vec![(99.0, 101.0)]
+        );
+
+        let mut below = level(Levels::Fixed(100.0), Some(Direction::Down), 0.001);
+        assert_eq!(
+            crossings(&mut below, &[99.0, 101.0, 99.0]),
+            vec![(101.0, 99.0)]
+        );
+    }
+
+    #[test]
+    fn inherit_keeps_the_anchor_of_the_same_level_rule() {
+        let mut old = level(Levels::Fixed(100.0), None, 0.01);
+        assert_eq!(crossings(&mut old, &[99.0, 100.5]).len(), 1);
+
+        // Still within hysteresis of the level it fired at.
+        let mut same = level(Levels::Fixed(100.0), None, 0.01);
+        same.inherit(&old);
+        assert!(crossings(&mut same, &[100.5, 99.5]).is_empty());
+
+        // A changed rule starts afresh.
+        let mut changed = level(Levels::Fixed(100.0), None, 0.002);
+        changed.inherit(&old);
+        assert_eq!(crossings(&mut changed, &[100.5, 99.5]).len(), 1);
+    }
+
+    #[test]
+    fn cooldown_lets_the_opposite_direction_through() {
+        let now = Instant::now();
+        let mut cooldown = Cooldown::new(Duration::from_secs(60), None);
+        cooldown.record(Direction::Down, 99.0, now);
+
+        let later = now + Duration::from_secs(10);
+        assert!(!cooldown.allows(Direction::Down, 95.0, later));
+        assert!(cooldown.allows(Direction::Up, 101.0, later));
+        assert!(cooldown.allows(Direction::Down, 95.0, now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn escalation_fires_at_exactly_the_step() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(1);
+        let mut cooldown = Cooldown::new(Duration::from_secs(60), Some(0.01));
+        cooldown.record(Direction::Down, 100.0, now);
+        cooldown.record(Direction::Up, 100.0, now);
+
+        assert!(!cooldown.allows(Direction::Down, 99.01, later));
+        assert!(cooldown.allows(Direction::Down, 99.0, later));
+        assert!(!cooldown.allows(Direction::Up, 100.99, later));
+        assert!(cooldown.allows(Direction::Up, 101.0, later));
+
+        // The next step counts from the escalated alert.
+        cooldown.record(Direction::Down, 99.0, later);
+        assert!(!cooldown.allows(Direction::Down, 98.5, later));
+        assert!(cooldown.allows(Direction::Down, 98.0, later));
+    }
+
+    #[test]
+    fn inherit_keeps_the_cooldown_across_a_reload() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+        let change = RuleKind::Change { threshold: 0.01 };
+        let mut old = rule(change.clone(), Cooldown::new(Duration::from_secs(60), None));
+        old.cooldown.record(Direction::Up, 101.0, now);
+
+        // A new threshold and escalation, but the same alert history.
+        let mut new = rule(
+            RuleKind::Change { threshold: 0.02 },
+            Cooldown::new(Duration::from_secs(60), Some(0.05)),
+        );
+        new.inherit(&old);
+        assert!(!new.cooldown.allows(Direction::Up, 102.0, later));
+        assert!(new.cooldown.allows(Direction::Down, 99.0, later));
+        assert!(new.cooldown.allows(Direction::Up, 106.1, later));
+        assert_eq!(new.cooldown.escalate, Some(0.05));
+    }
+
+    fn window(threshold: f64, from: WindowReference, prices: &[f64]) -> Option<(f64, f64)> {
+        let start = Instant::now();
+        let mut history = PriceHistory::new(Duration::from_secs(600));
+        for (i, price) in prices.iter().enumerate() {
+            history.push(start + Duration::from_secs(i as u64), *price);
+        }
+        let now = start + Duration::from_secs(prices.len() as u64 - 1);
+        let kind = RuleKind::Window {
+            threshold,
+            window: Duration::from_secs(600),
+            from,
+        };
+        let mut rule = rule(kind, Cooldown::new(Duration::ZERO, None));
+        let current = *prices.last().unwrap();
+        rule.should_alert(None, None, current, &history, now)
+            .map(|change| (change.from, change.to))
+    }
+
+    #[test]
+    fn high_low_measures_the_bigger_of_the_rise_and_the_drop() {
+        // Up 10% from the low beats down 5% from the high.
+        let prices = [100.0, 115.5, 105.0, 110.0];
+        assert_eq!(
+            window(0.05, WindowReference::HighLow, &prices),
+            Some((100.0, 110.0))
+        );
+        // Down 9% from the high beats up 3% from the low.
+        let prices = [100.0, 113.0, 103.0];
+        assert_eq!(
+            window(0.05, WindowReference::HighLow, &prices),
+            Some((113.0, 103.0))
+        );
+        // Neither reaches the threshold.
+        assert_eq!(
+            window(0.05, WindowReference::HighLow, &[100.0, 103.0, 101.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn window_reference_picks_the_price_to_measure_from() {
+        let prices = [100.0, 120.0, 90.0, 108.0];
+        assert_eq!(
+            window(0.05, WindowReference::Open, &prices),
+            Some((100.0, 108.0))
+        );
+        assert_eq!(
+            window(0.05, WindowReference::High, &prices),
+            Some((120.0, 108.0))
+        );
+        assert_eq!(
+            window(0.05, WindowReference::Low, &prices),
+            Some((90.0, 108.0))
+        );
+    }
+
+    #[test]
+    fn magnitude_steps_at_each_boundary() {
+        let cases = [
+            (100.4999, 1),
+            (100.5, 2),
+            (101.0, 3),
+            (102.0, 4),
+            (104.9999, 4),
+            (105.0, 5),
+            (150.0, 5),
+            (99.6, 1),
+            (99.5, 2),
+            (95.0, 5),
+        ];
+        for (to, magnitude) in cases {
+            assert_eq!(Move::new(100.0, to).magnitude(), magnitude, "{}", to);
+        }
+    }
+}
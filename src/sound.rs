@@ -0,0 +1,195 @@
+use crate::rule::{Direction, Move};
+use anyhow::{Context, Result};
+use rodio::{
+    source::{SineWave, Zero},
+    Decoder, Source,
+};
+use std::{fmt, fs, io::Cursor, path::Path, sync::Arc, time::Duration};
+
+static DEFAULT_ALERT: &[u8] = include_bytes!("alert.mp3");
+
+/// Sound name that selects [`Sound::Tone`] instead of a file.
+pub const TONE: &str = "tone";
+
+const BEEP: Duration = Duration::from_millis(120);
+const GAP: Duration = Duration::from_millis(60);
+const TONE_SAMPLE_RATE: u32 = 48_000;
+// Each beep of a rise is this many semitones above the previous one, each
+// beep of a drop the same amount below.
+const BEEP_STEP: f32 = 3.0;
+
+pub type Samples = Box<dyn Source<Item = f32> + Send>;
+
+/// An alert sound: a clip held in memory, either the built-in one or a file
+/// read and checked when the config is loaded, or synthesized beeps.
+#[derive(Clone)]
+pub enum Sound {
+    Clip {
+        name: Arc<str>,
+        data: Arc<[u8]>,
+    },
+    /// Beeps that climb in pitch for a rise and fall for a drop, with more
+    /// beeps the bigger the move, so its size can be heard.
+    Tone,
+}
+
+impl Sound {
+    /// Reads `path` and decodes it fully, so a missing, unsupported or corrupt
+    /// file is reported at startup instead of at the first alert.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data: Arc<[u8]> = fs::read(path)
+            .with_context(|| format!("Failed to read sound {}", path.display()))?
+            .into();
+        let samples = decode(&data)
+            .map(|decoder| decoder.convert_samples::<f32>().count())
+            .with_context(|| {
+                format!(
+                    "Unsupported sound {}, expected mp3, wav, ogg or flac",
+                    path.display()
+                )
+            })?;
+        if samples == 0 {
+            anyhow::bail!("Sound {} is empty", path.display());
+        }
+        Ok(Sound::Clip {
+            name: path.display().to_string().into(),
+            data,
+        })
+    }
+
+    /// The sources to play, in order, to announce `change`.
+    pub fn sources(&self, change: &Move) -> Result<Vec<Samples>> {
+        match self {
+            Sound::Clip { data, .. } => Ok(vec![Box::new(decode(data)?.convert_samples())]),
+            Sound::Tone => Ok(tones(change)),
+        }
+    }
+}
+
+fn decode(data: &Arc<[u8]>) -> Result<Decoder<Cursor<Arc<[u8]>>>> {
+    Ok(Decoder::new(Cursor::new(data.clone()))?)
+}
+
+fn tones(change: &Move) -> Vec<Samples> {
+    frequencies(change)
+        .into_iter()
+        .flat_map(|frequency| {
+            let beep: Samples = Box::new(SineWave::new(frequency).take_duration(BEEP).amplify(0.3));
+            let gap: Samples = Box::new(Zero::<f32>::new(1, TONE_SAMPLE_RATE).take_duration(GAP));
+            [beep, gap]
+        })
+        .collect()
+}
+
+// One beep per step of the move's magnitude. Rises climb from A5, drops fall
+// from it.
+fn frequencies(change: &Move) -> Vec<f32> {
+    let step = match change.direction {
+        Direction::Up => BEEP_STEP,
+        Direction::Down => -BEEP_STEP,
+    };
+    (0..change.magnitude())
+        .map(|i| 880.0 * 2f32.powf(step * i as f32 / 12.0))
+        .collect()
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Sound::Clip {
+            name: "built-in".into(),
+            data: DEFAULT_ALERT.into(),
+        }
+    }
+}
+
+impl PartialEq for Sound {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Sound::Clip { name, .. }, Sound::Clip { name: other, .. }) => name == other,
+            (Sound::Tone, Sound::Tone) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Sound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sound::Clip { name, .. } => f.write_str(name),
+            Sound::Tone => f.write_str(TONE),
+        }
+    }
+}
+
+impl fmt::Debug for Sound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sound({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A file in the temp directory with `contents`, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("subtle-alert-{}-{}", std::process::id(), name));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_sounds() {
+        for file in [
+            TempFile::new("notes.mp3", b"not a sound at all"),
+            TempFile::new("empty.wav", b""),
+        ] {
+            let e = format!("{:#}", Sound::load(&file.0).unwrap_err());
+            assert!(e.contains(&file.0.display().to_string()), "{}", e);
+        }
+        let e = Sound::load(Path::new("/nonexistent/alert.mp3")).unwrap_err();
+        assert!(format!("{:#}", e).contains("/nonexistent/alert.mp3"));
+    }
+
+    #[test]
+    fn built_in_sound_decodes() {
+        let samples = decode(&DEFAULT_ALERT.into())
+            .unwrap()
+            .convert_samples::<f32>()
+            .count();
+        assert!(samples > 0);
+    }
+
+    #[test]
+    fn beeps_once_per_step_of_the_move() {
+        for (to, beeps) in [(100.1, 1), (101.0, 3), (110.0, 5), (98.0, 4)] {
+            let change = Move::new(100.0, to);
+            assert_eq!(frequencies(&change).len(), beeps, "{}", to);
+            // A beep and the gap after it.
+            assert_eq!(tones(&change).len(), 2 * beeps, "{}", to);
+        }
+    }
+
+    #[test]
+    fn pitch_follows_the_direction() {
+        let up = frequencies(&Move::new(100.0, 110.0));
+        assert_eq!(up[0], 880.0);
+        assert!(up.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", up);
+
+        let down = frequencies(&Move::new(100.0, 90.0));
+        assert_eq!(down[0], 880.0);
+        assert!(down.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", down);
+    }
+}
@@ -0,0 +1,281 @@
+use super::{PriceSource, Quote};
+use crate::pair::Pair;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::{debug, warn};
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateMethod {
+    Median,
+    VolumeWeighted,
+}
+
+impl FromStr for AggregateMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "median" => Ok(AggregateMethod::Median),
+            "vwap" | "volume-weighted" => Ok(AggregateMethod::VolumeWeighted),
+            _ => anyhow::bail!("Unknown aggregate method {:?}, expected median or vwap", s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AggregateMethod {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Polls several sources concurrently and reports their consensus price.
+///
+/// Quotes further than `max_deviation` (as a fraction) from the median of all
+/// quotes are discarded before aggregating, so one exchange wicking does not
+/// move the result. That takes at least three quotes; when only two come back
+/// and they disagree, the fetch fails rather than split the difference.
+pub struct Aggregator {
+    sources: Vec<Box<dyn PriceSource>>,
+    method: AggregateMethod,
+    max_deviation: f64,
+}
+
+impl Aggregator {
+    pub fn new(
+        sources: Vec<Box<dyn PriceSource>>,
+        method: AggregateMethod,
+        max_deviation: f64,
+    ) -> Self {
+        Self {
+            sources,
+            method,
+            max_deviation,
+        }
+    }
+
+    fn aggregate(&self, pair: &Pair, quotes: &[Quote]) -> Result<Quote> {
+        if quotes.is_empty() {
+            anyhow::bail!("No price source returned a {} quote", pair);
+        }
+
+        let accepted = reject_outliers(quotes, self.max_deviation);
+        if accepted.is_empty() {
+            anyhow::bail!(
+                "Price sources disagree on {} by more than {:.2}%",
+                pair,
+                self.max_deviation * 100.0
+            );
+        }
+        for quote in quotes.iter().filter(|q| !accepted.contains(q)) {
+            warn!(
+                "Discarding {} {} quote {:.2} as an outlier",
+                quote.source, pair, quote.price
+            );
+        }
+
+        let price = match self.method {
+            AggregateMethod::Median => median(accepted.iter().map(|q| q.price)),
+            AggregateMethod::VolumeWeighted => volume_weighted(&accepted),
+        };
+        debug!(
+            "Aggregated {} of {} {} quotes into {:.2}",
+            accepted.len(),
+            quotes.len(),
+            pair,
+            price
+        );
+
+        Ok(Quote {
+            source: self.name(),
+            pair: pair.clone(),
+            price,
+            volume: accepted.iter().map(|q| q.volume).sum(),
+        })
+    }
+}
+
+#[async_trait]
+impl PriceSource for Aggregator {
+    fn name(&self) -> &'static str {
+        "aggregate"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let mut quotes = self.fetch_quotes(std::slice::from_ref(pair)).await?;
+        Ok(quotes.remove(0))
+    }
+
+    async fn fetch_quotes(&self, pairs: &[Pair]) -> Result<Vec<Quote>> {
+        let results = join_all(self.sources.iter().map(|source| source.fetch_quotes(pairs))).await;
+
+        let mut quotes = Vec::new();
+        for (source, result) in self.sources.iter().zip(results) {
+            match result {
+                Ok(source_quotes) => quotes.extend(source_quotes),
+                Err(e) => warn!("Failed to fetch prices from {}: {}", source.name(), e),
+            }
+        }
+
+        let mut aggregated = Vec::with_capacity(pairs.len());
+        let mut last_error = None;
+        for pair in pairs {
+            let pair_quotes: Vec<_> = quotes.iter().filter(|q| &q.pair == pair).cloned().collect();
+            match self.aggregate(pair, &pair_quotes) {
+                Ok(quote) => aggregated.push(quote),
+                Err(e) => {
+                    warn!("{}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) if aggregated.is_empty() => Err(e),
+            _ => Ok(aggregated),
+        }
+    }
+}
+
+fn median(prices: impl Iterator<Item = f64>) -> f64 {
+    let mut prices: Vec<f64> = prices.collect();
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    if prices.len().is_multiple_of(2) {
+        (prices[mid - 1] + prices[mid]) / 2.0
+    } else {
+        prices[mid]
+    }
+}
+
+fn reject_outliers(quotes: &[Quote], max_deviation: f64) -> Vec<Quote> {
+    let center = median(quotes.iter().map(|q| q.price));
+    let near = |q: &&Quote| (q.price - center).abs() / center <= max_deviation;
+
+    // With two quotes there is no majority to side with, so if they disagree
+    // neither can be trusted.
+    if quotes.len() == 2 && !quotes.iter().all(|q| near(&q)) {
+        return Vec::new();
+    }
+    quotes.iter().filter(near).cloned().collect()
+}
+
+// Falls back to the median when any accepted source lacks volume data.
+fn volume_weighted(quotes: &[Quote]) -> f64 {
+    let volumes: Option<Vec<f64>> = quotes.iter().map(|q| q.volume).collect();
+    match volumes {
+        Some(volumes) if volumes.iter().sum::<f64>() > 0.0 => {
+            let total: f64 = volumes.iter().sum();
+            quotes
+                .iter()
+                .zip(&volumes)
+                .map(|(q, v)| q.price * v)
+                .sum::<f64>()
+                / total
+        }
+        _ => median(quotes.iter().map(|q| q.price)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(source: &'static str, price: f64, volume: Option<f64>) -> Quote {
+        Quote {
+            source,
+            pair: Pair::new("BTC", "USD"),
+            price,
+            volume,
+        }
+    }
+
+    fn prices(quotes: &[Quote]) -> Vec<f64> {
+        quotes.iter().map(|q| q.price).collect()
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median([3.0, 1.0, 2.0].into_iter()), 2.0);
+        assert_eq!(median([4.0, 1.0, 3.0, 2.0].into_iter()), 2.5);
+        assert_eq!(median([7.0].into_iter()), 7.0);
+    }
+
+    #[test]
+    fn rejects_a_wick_among_three() {
+        let quotes = [
+            quote("a", 100.0, None),
+            quote("b", 100.5, None),
+            quote("c", 90.0, None),
+        ];
+        assert_eq!(prices(&reject_outliers(&quotes, 0.01)), vec![100.0, 100.5]);
+    }
+
+    #[test]
+    fn rejects_outliers_among_an_even_count() {
+        let quotes = [
+            quote("a", 100.0, None),
+            quote("b", 100.2, None),
+            quote("c", 100.4, None),
+            quote("d", 110.0, None),
+        ];
+        assert_eq!(
+            prices(&reject_outliers(&quotes, 0.01)),
+            vec![100.0, 100.2, 100.4]
+        );
+    }
+
+    #[test]
+    fn rejects_everything_when_no_quotes_agree() {
+        let quotes = [
+            quote("a", 90.0, None),
+            quote("b", 100.0, None),
+            quote("c", 110.0, None),
+            quote("d", 120.0, None),
+        ];
+        assert!(reject_outliers(&quotes, 0.01).is_empty());
+    }
+
+    #[test]
+    fn two_quotes_must_agree() {
+        let agree = [quote("a", 100.0, None), quote("b", 101.0, None)];
+        assert_eq!(prices(&reject_outliers(&agree, 0.01)), vec![100.0, 101.0]);
+
+        let wick = [quote("a", 100.0, None), quote("b", 95.0, None)];
+        assert!(reject_outliers(&wick, 0.01).is_empty());
+
+        let single = [quote("a", 100.0, None)];
+        assert_eq!(prices(&reject_outliers(&single, 0.01)), vec![100.0]);
+    }
+
+    #[test]
+    fn two_disagreeing_sources_fail_the_fetch() {
+        let aggregator = Aggregator::new(Vec::new(), AggregateMethod::Median, 0.01);
+        let pair = Pair::new("BTC", "USD");
+        let wick = [quote("a", 100.0, None), quote("b", 95.0, None)];
+        assert!(aggregator.aggregate(&pair, &wick).is_err());
+    }
+
+    #[test]
+    fn volume_weighted_price() {
+        let quotes = [quote("a", 100.0, Some(3.0)), quote("b", 104.0, Some(1.0))];
+        assert_eq!(volume_weighted(&quotes), 101.0);
+    }
+
+    #[test]
+    fn volume_weighted_falls_back_to_the_median() {
+        let missing = [
+            quote("a", 100.0, Some(3.0)),
+            quote("b", 104.0, None),
+            quote("c", 101.0, Some(1.0)),
+        ];
+        assert_eq!(volume_weighted(&missing), 101.0);
+
+        let zero = [quote("a", 100.0, Some(0.0)), quote("b", 104.0, Some(0.0))];
+        assert_eq!(volume_weighted(&zero), 102.0);
+    }
+}
@@ -0,0 +1,99 @@
+use super::{parse_price, PriceSource, Quote};
+use crate::pair::Pair;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+const BASE_URL: &str = "https://api.binance.com";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker {
+    last_price: String,
+    volume: String,
+}
+
+pub struct Binance {
+    client: Client,
+    base_url: String,
+}
+
+impl Binance {
+    pub fn new(client: Client) -> Self {
+        Self::with_base_url(client, BASE_URL)
+    }
+
+    /// Talks to `base_url` instead of Binance's public API, e.g. a mock.
+    pub fn with_base_url(client: Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for Binance {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let ticker: BinanceTicker = self
+            .client
+            .get(format!(
+                "{}/api/v3/ticker/24hr?symbol={}{}",
+                self.base_url,
+                pair.base,
+                quote_symbol(&pair.quote)
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Quote {
+            source: self.name(),
+            pair: pair.clone(),
+            price: parse_price(&ticker.last_price)?,
+            volume: ticker.volume.parse().ok(),
+        })
+    }
+}
+
+// Binance has no USD spot books, USDT is the closest proxy.
+fn quote_symbol(quote: &str) -> &str {
+    match quote {
+        "USD" => "USDT",
+        _ => quote,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const TICKER: &str = r#"{"symbol":"BTCUSDT","priceChange":"1000.00","priceChangePercent":"1.000","weightedAvgPrice":"100500.00","lastPrice":"101000.00000000","lastQty":"0.01","openPrice":"100000.00","highPrice":"102000.00","lowPrice":"99000.00","volume":"20000.50000000","quoteVolume":"2010000000.00","openTime":1760436000000,"closeTime":1760522400000,"count":1000000}"#;
+
+    #[tokio::test]
+    async fn asks_for_usdt_in_place_of_usd() {
+        let (url, mut requests) = testing::serve(&[(200, TICKER), (200, TICKER)]).await;
+        let binance = Binance::with_base_url(Client::new(), url);
+
+        let quote = binance.fetch_quote(&Pair::new("BTC", "USD")).await.unwrap();
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/api/v3/ticker/24hr?symbol=BTCUSDT"
+        );
+        assert_eq!((quote.price, quote.volume), (101000.0, Some(20000.5)));
+
+        binance.fetch_quote(&Pair::new("BTC", "EUR")).await.unwrap();
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/api/v3/ticker/24hr?symbol=BTCEUR"
+        );
+    }
+}
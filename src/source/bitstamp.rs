@@ -0,0 +1,88 @@
+use super::{parse_price, PriceSource, Quote};
+use crate::pair::Pair;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+const BASE_URL: &str = "https://www.bitstamp.net";
+
+#[derive(Debug, Deserialize)]
+struct BitstampTicker {
+    last: String,
+    volume: String,
+}
+
+pub struct Bitstamp {
+    client: Client,
+    base_url: String,
+}
+
+impl Bitstamp {
+    pub fn new(client: Client) -> Self {
+        Self::with_base_url(client, BASE_URL)
+    }
+
+    /// Talks to `base_url` instead of Bitstamp's public API, e.g. a mock.
+    pub fn with_base_url(client: Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for Bitstamp {
+    fn name(&self) -> &'static str {
+        "bitstamp"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let ticker: BitstampTicker = self
+            .client
+            .get(format!(
+                "{}/api/v2/ticker/{}{}/",
+                self.base_url,
+                pair.base.to_ascii_lowercase(),
+                pair.quote.to_ascii_lowercase()
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Quote {
+            source: self.name(),
+            pair: pair.clone(),
+            price: parse_price(&ticker.last)?,
+            volume: ticker.volume.parse().ok(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn parses_the_ticker() {
+        let (url, mut requests) = testing::serve(&[(
+            200,
+            r#"{"timestamp":"1760522400","open":"100000","high":"102000","low":"99000","last":"101000","volume":"1500.5","vwap":"100600","bid":"100999","ask":"101001","open_24":"100100","percent_change_24":"0.90"}"#,
+        )])
+        .await;
+        let quote = Bitstamp::with_base_url(Client::new(), url)
+            .fetch_quote(&Pair::new("BTC", "USD"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/api/v2/ticker/btcusd/"
+        );
+        assert_eq!((quote.price, quote.volume), (101000.0, Some(1500.5)));
+    }
+}
@@ -0,0 +1,86 @@
+use super::{parse_price, PriceSource, Quote};
+use crate::pair::Pair;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+const BASE_URL: &str = "https://api.exchange.coinbase.com";
+
+#[derive(Debug, Deserialize)]
+struct CoinbaseTicker {
+    price: String,
+    volume: String,
+}
+
+pub struct Coinbase {
+    client: Client,
+    base_url: String,
+}
+
+impl Coinbase {
+    pub fn new(client: Client) -> Self {
+        Self::with_base_url(client, BASE_URL)
+    }
+
+    /// Talks to `base_url` instead of Coinbase's public API, e.g. a mock.
+    pub fn with_base_url(client: Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for Coinbase {
+    fn name(&self) -> &'static str {
+        "coinbase"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let ticker: CoinbaseTicker = self
+            .client
+            .get(format!(
+                "{}/products/{}-{}/ticker",
+                self.base_url, pair.base, pair.quote
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Quote {
+            source: self.name(),
+            pair: pair.clone(),
+            price: parse_price(&ticker.price)?,
+            volume: ticker.volume.parse().ok(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn parses_the_ticker() {
+        let (url, mut requests) = testing::serve(&[(
+            200,
+            r#"{"ask":"101000.51","bid":"101000.50","volume":"12345.67","trade_id":123,"price":"101000.50","size":"0.01","time":"2026-10-15T10:00:00.000000Z"}"#,
+        )])
+        .await;
+        let quote = Coinbase::with_base_url(Client::new(), url)
+            .fetch_quote(&Pair::new("BTC", "EUR"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/products/BTC-EUR/ticker"
+        );
+        assert_eq!((quote.price, quote.volume), (101000.5, Some(12345.67)));
+    }
+}
@@ -0,0 +1,137 @@
+use super::{check_price, PriceSource, Quote};
+use crate::pair::Pair;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::collections::HashMap;
+
+const BASE_URL: &str = "https://api.coingecko.com";
+
+// CoinGecko prices coins by id rather than ticker symbol.
+const COIN_IDS: [(&str, &str); 10] = [
+    ("BTC", "bitcoin"),
+    ("ETH", "ethereum"),
+    ("SOL", "solana"),
+    ("LTC", "litecoin"),
+    ("XRP", "ripple"),
+    ("DOGE", "dogecoin"),
+    ("ADA", "cardano"),
+    ("DOT", "polkadot"),
+    ("BCH", "bitcoin-cash"),
+    ("XMR", "monero"),
+];
+
+// id -> { "usd": 64000.0, "usd_24h_vol": 1.2e10 }
+type SimplePrice = HashMap<String, HashMap<String, Option<f64>>>;
+
+pub struct CoinGecko {
+    client: Client,
+    base_url: String,
+}
+
+impl CoinGecko {
+    pub fn new(client: Client) -> Self {
+        Self::with_base_url(client, BASE_URL)
+    }
+
+    /// Talks to `base_url` instead of CoinGecko's public API, e.g. a mock.
+    pub fn with_base_url(client: Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for CoinGecko {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let id = coin_id(&pair.base)?;
+        let currency = pair.quote.to_ascii_lowercase();
+        let mut response: SimplePrice = self
+            .client
+            .get(format!(
+                "{}/api/v3/simple/price?ids={}&vs_currencies={}&include_24hr_vol=true",
+                self.base_url, id, currency
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let prices = response.remove(id).unwrap_or_default();
+        let price = prices
+            .get(&currency)
+            .copied()
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("CoinGecko has no {} price", pair))
+            .and_then(check_price)?;
+        let volume = prices
+            .get(&format!("{}_24h_vol", currency))
+            .copied()
+            .flatten();
+
+        Ok(Quote {
+            source: self.name(),
+            pair: pair.clone(),
+            price,
+            // CoinGecko reports volume in the quote currency.
+            volume: volume.map(|vol| vol / price),
+        })
+    }
+}
+
+fn coin_id(symbol: &str) -> Result<&'static str> {
+    COIN_IDS
+        .iter()
+        .find(|(known, _)| *known == symbol)
+        .map(|(_, id)| *id)
+        .ok_or_else(|| anyhow::anyhow!("CoinGecko source does not know the {} coin", symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn converts_volume_to_the_base_asset() {
+        let (url, mut requests) = testing::serve(&[(
+            200,
+            r#"{"bitcoin":{"eur":100000.0,"eur_24h_vol":2000000000.0}}"#,
+        )])
+        .await;
+        let quote = CoinGecko::with_base_url(Client::new(), url)
+            .fetch_quote(&Pair::new("BTC", "EUR"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests.recv().await.unwrap().path,
+            "/api/v3/simple/price?ids=bitcoin&vs_currencies=eur&include_24hr_vol=true"
+        );
+        assert_eq!((quote.price, quote.volume), (100000.0, Some(20000.0)));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_and_zero_prices() {
+        let (url, _requests) = testing::serve(&[
+            (200, r#"{"bitcoin":{}}"#),
+            (200, r#"{"bitcoin":{"usd":0.0}}"#),
+        ])
+        .await;
+        let coingecko = CoinGecko::with_base_url(Client::new(), url);
+        let btc = Pair::new("BTC", "USD");
+        assert!(coingecko.fetch_quote(&btc).await.is_err());
+        assert!(coingecko.fetch_quote(&btc).await.is_err());
+        assert!(coingecko
+            .fetch_quote(&Pair::new("PEPE", "USD"))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use super::{parse_price, PriceSource, Quote};
+use crate::{metrics, pair::Pair};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashMap, sync::Mutex};
+
+const BASE_URL: &str = "https://api.kraken.com";
+
+#[derive(Debug, Deserialize)]
+struct KrakenResponse<T> {
+    error: Vec<String>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetPair {
+    altname: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TickerInfo {
+    c: Vec<String>, // c = last trade closed price
+    v: Vec<String>, // v = volume today, last 24 hours
+}
+
+pub struct Kraken {
+    client: Client,
+    base_url: String,
+    // Pair -> Kraken's canonical pair name, e.g. BTC/USD -> XXBTZUSD.
+    pair_names: Mutex<HashMap<Pair, String>>,
+}
+
+impl Kraken {
+    pub fn new(client: Client) -> Self {
+        Self::with_base_url(client, BASE_URL)
+    }
+
+    /// Talks to `base_url` instead of Kraken's public API, e.g. a mock.
+    pub fn with_base_url(client: Client, base_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+            pair_names: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response: KrakenResponse<T> = self
+            .client
+            .get(format!("{}/0/public/{}", self.base_url, path))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if !response.error.is_empty() {
+            anyhow::bail!("Kraken API error: {:?}", response.error);
+        }
+
+        response
+            .result
+            .ok_or_else(|| anyhow::anyhow!("Kraken response has no result"))
+    }
+
+    /// Looks up the name Kraken uses for `pair` in its responses, which
+    /// usually differs from what we ask for (X/Z prefixed asset codes).
+    async fn resolve(&self, pair: &Pair) -> Result<String> {
+        if let Some(name) = self.pair_names.lock().unwrap().get(pair) {
+            return Ok(name.clone());
+        }
+
+        let requested = format!("{}{}", asset_code(&pair.base), asset_code(&pair.quote));
+        let pairs: HashMap<String, AssetPair> = self
+            .get(&format!("AssetPairs?pair={}", requested))
+            .await
+            .with_context(|| format!("Kraken does not list {}", pair))?;
+        let (name, info) = pairs
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Kraken does not list {}", pair))?;
+        debug!("Kraken lists {} as {} ({})", pair, name, info.altname);
+
+        self.pair_names
+            .lock()
+            .unwrap()
+            .insert(pair.clone(), name.clone());
+        Ok(name)
+    }
+}
+
+#[async_trait]
+impl PriceSource for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let mut quotes = self.fetch_quotes(std::slice::from_ref(pair)).await?;
+        Ok(quotes.remove(0))
+    }
+
+    // The Ticker endpoint takes a comma separated list, so every pair is
+    // fetched in a single request.
+    async fn fetch_quotes(&self, pairs: &[Pair]) -> Result<Vec<Quote>> {
+        let mut resolved = Vec::with_capacity(pairs.len());
+        let mut unlisted = Vec::new();
+        for pair in pairs {
+            match self.resolve(pair).await {
+                Ok(name) => resolved.push((pair, name)),
+                Err(e) if pairs.len() > 1 => {
+                    warn!("{:#}", e);
+                    unlisted.push(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if resolved.is_empty() {
+            anyhow::bail!("Kraken lists none of the requested pairs");
+        }
+        // The other pairs are still fetched, so the caller won't count these.
+        for e in &unlisted {
+            metrics::fetch_failed(self.name(), e);
+        }
+        let names: Vec<_> = resolved.iter().map(|(_, name)| name.as_str()).collect();
+
+        let mut tickers: HashMap<String, TickerInfo> = self
+            .get(&format!("Ticker?pair={}", names.join(",")))
+            .await?;
+
+        resolved
+            .iter()
+            .map(|(pair, name)| {
+                let ticker = tickers
+                    .remove(name)
+                    .ok_or_else(|| anyhow::anyhow!("Kraken ticker has no {} entry", name))?;
+                let last = ticker
+                    .c
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("Kraken ticker has no last trade"))?;
+
+                Ok(Quote {
+                    source: self.name(),
+                    pair: (*pair).clone(),
+                    price: parse_price(last)?,
+                    volume: ticker.v.get(1).and_then(|v| v.parse().ok()),
+                })
+            })
+            .collect()
+    }
+}
+
+fn asset_code(symbol: &str) -> &str {
+    match symbol {
+        "BTC" => "XBT",
+        "DOGE" => "XDG",
+        _ => symbol,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const BTC_PAIR: &str = r#"{"error":[],"result":{"XXBTZUSD":{"altname":"XBTUSD","wsname":"XBT/USD","base":"XXBT","quote":"ZUSD"}}}"#;
+    const ETH_PAIR: &str = r#"{"error":[],"result":{"XETHZUSD":{"altname":"ETHUSD","wsname":"ETH/USD","base":"XETH","quote":"ZUSD"}}}"#;
+    const TICKER: &str = r#"{"error":[],"result":{
+        "XXBTZUSD":{"a":["101000.20000","1","1.000"],"b":["101000.10000","2","2.000"],"c":["101000.10000","0.00100000"],"v":["812.5","2500.25"],"p":["100500.0","100400.0"],"t":[1000,2000],"l":["99000.0","98000.0"],"h":["102000.0","103000.0"],"o":"100000.0"},
+        "XETHZUSD":{"a":["3000.20","1","1.000"],"b":["3000.10","2","2.000"],"c":["3000.15000","0.50000000"],"v":["10000.0","40000.5"],"p":["2990.0","2980.0"],"t":[500,900],"l":["2900.0","2800.0"],"h":["3100.0","3200.0"],"o":"2950.0"}}}"#;
+
+    #[tokio::test]
+    async fn resolves_pair_names_and_batches_tickers() {
+        let (url, mut requests) = testing::serve(&[
+            (200, BTC_PAIR),
+            (200, ETH_PAIR),
+            (200, TICKER),
+            (200, TICKER),
+        ])
+        .await;
+        let kraken = Kraken::with_base_url(Client::new(), url);
+        let pairs = [Pair::new("BTC", "USD"), Pair::new("ETH", "USD")];
+
+        let quotes = kraken.fetch_quotes(&pairs).await.unwrap();
+        assert_eq!(
+            quotes,
+            vec![
+                Quote {
+                    source: "kraken",
+                    pair: Pair::new("BTC", "USD"),
+                    price: 101000.1,
+                    volume: Some(2500.25),
+                },
+                Quote {
+                    source: "kraken",
+                    pair: Pair::new("ETH", "USD"),
+                    price: 3000.15,
+                    volume: Some(40000.5),
+                },
+            ]
+        );
+        let paths: Vec<_> = [
+            requests.recv().await.unwrap(),
+            requests.recv().await.unwrap(),
+            requests.recv().await.unwrap(),
+        ]
+        .into_iter()
+        .map(|request| request.path)
+        .collect();
+        assert_eq!(
+            paths,
+            [
+                "/0/public/AssetPairs?pair=XBTUSD",
+                "/0/public/AssetPairs?pair=ETHUSD",
+                "/0/public/Ticker?pair=XXBTZUSD,XETHZUSD",
+            ]
+        );
+
+        // Resolved names are remembered.
+        kraken.fetch_quotes(&pairs).await.unwrap();
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.path, "/0/public/Ticker?pair=XXBTZUSD,XETHZUSD");
+    }
+
+    #[tokio::test]
+    async fn reports_api_errors() {
+        let (url, _requests) =
+            testing::serve(&[(200, r#"{"error":["EQuery:Unknown asset pair"]}"#)]).await;
+        let kraken = Kraken::with_base_url(Client::new(), url);
+        let e = kraken
+            .fetch_quote(&Pair::new("FOO", "USD"))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", e).contains("EQuery:Unknown asset pair"),
+            "{:#}",
+            e
+        );
+    }
+}
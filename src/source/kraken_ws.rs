@@ -0,0 +1,284 @@
+use super::check_price;
+use crate::pair::Pair;
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::{sync::mpsc, time};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+pub const WS_URL: &str = "wss://ws.kraken.com/v2";
+
+// Kraken sends a heartbeat every second, so a quiet socket is a dead one.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct ChannelMessage {
+    channel: String,
+    #[serde(default)]
+    data: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Trade {
+    symbol: String,
+    price: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    symbol: String,
+    last: f64,
+}
+
+/// Streams prices from Kraken's WebSocket `ticker` and `trade` channels.
+pub struct KrakenStream {
+    url: String,
+    pairs: Vec<Pair>,
+}
+
+impl KrakenStream {
+    pub fn new(url: impl Into<String>, pairs: Vec<Pair>) -> Self {
+        Self {
+            url: url.into(),
+            pairs,
+        }
+    }
+
+    /// Sends every trade price to `prices`, reconnecting with exponential
+    /// backoff until the receiver is dropped.
+    pub async fn run(&self, prices: mpsc::Sender<(Pair, f64)>) {
+        let mut backoff = MIN_BACKOFF;
+        while !prices.is_closed() {
+            match self.stream(&prices, &mut backoff).await {
+                Ok(()) => {
+                    warn!("Kraken WebSocket closed, reconnecting");
+                    backoff = MIN_BACKOFF;
+                }
+                Err(e) => warn!(
+                    "Kraken WebSocket error: {:#}, reconnecting in {:?}",
+                    e, backoff
+                ),
+            }
+            time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    // Resets `backoff` once the connection delivers data, so errors from
+    // earlier connections don't delay the next reconnect.
+    async fn stream(
+        &self,
+        prices: &mpsc::Sender<(Pair, f64)>,
+        backoff: &mut Duration,
+    ) -> Result<()> {
+        let (mut socket, _) = connect_async(self.url.as_str())
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.url))?;
+        info!("Connected to {}", self.url);
+
+        let symbols: Vec<_> = self.pairs.iter().map(Pair::to_string).collect();
+        for channel in ["ticker", "trade"] {
+            let subscribe = json!({
+                "method": "subscribe",
+                "params": { "channel": channel, "symbol": symbols },
+            });
+            socket.send(Message::text(subscribe.to_string())).await?;
+        }
+
+        loop {
+            let message = match time::timeout(READ_TIMEOUT, socket.next()).await {
+                Ok(Some(message)) => message?,
+                Ok(None) => return Ok(()),
+                Err(_) => anyhow::bail!("No message received for {:?}", READ_TIMEOUT),
+            };
+
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => return Ok(()),
+                _ => continue,
+            };
+
+            *backoff = MIN_BACKOFF;
+            for price in parse_prices(&text) {
+                if prices.send(price).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn parse_prices(text: &str) -> Vec<(Pair, f64)> {
+    // Acks and errors for our subscriptions have no `channel` field.
+    let message: ChannelMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(_) => {
+            debug!("Ignoring Kraken message: {}", text);
+            return Vec::new();
+        }
+    };
+
+    let parse: fn(serde_json::Value) -> Result<(Pair, f64)> = match message.channel.as_str() {
+        "trade" => |trade| {
+            let trade: Trade = serde_json::from_value(trade)?;
+            Ok((trade.symbol.parse()?, check_price(trade.price)?))
+        },
+        "ticker" => |ticker| {
+            let ticker: Ticker = serde_json::from_value(ticker)?;
+            Ok((ticker.symbol.parse()?, check_price(ticker.last)?))
+        },
+        "status" | "heartbeat" => return Vec::new(),
+        other => {
+            debug!("Ignoring message on Kraken channel {}", other);
+            return Vec::new();
+        }
+    };
+    // One bad entry shouldn't cost the rest of the message, or the connection.
+    message
+        .data
+        .into_iter()
+        .filter_map(|entry| match parse(entry.clone()) {
+            Ok(price) => Some(price),
+            Err(e) => {
+                warn!(
+                    "Skipping Kraken {} entry {}: {:#}",
+                    message.channel, entry, e
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // Takes the subscriptions of one client, sends it `messages` and hangs up.
+    async fn serve_once(listener: &TcpListener, messages: &[&str]) -> Vec<String> {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let mut subscriptions = Vec::new();
+        for _ in 0..2 {
+            let message = socket.next().await.unwrap().unwrap();
+            subscriptions.push(message.into_text().unwrap().to_string());
+        }
+        for message in messages {
+            socket.send(Message::text(*message)).await.unwrap();
+        }
+        socket.close(None).await.unwrap();
+        subscriptions
+    }
+
+    #[tokio::test]
+    async fn streams_prices_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let subscriptions = serve_once(
+                &listener,
+                &[
+                    r#"{"method":"subscribe","result":{"channel":"trade"},"success":true}"#,
+                    r#"{"channel":"heartbeat"}"#,
+                    r#"{"channel":"ticker","type":"snapshot","data":[{"symbol":"BTC/USD","last":100000.0}]}"#,
+                    r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","price":100001.5,"qty":0.1},{"symbol":"ETH/EUR","price":3000.0,"qty":1.0}]}"#,
+                ],
+            )
+            .await;
+            serve_once(
+                &listener,
+                &[r#"{"channel":"trade","type":"update","data":[{"symbol":"BTC/USD","price":99000.0,"qty":0.2}]}"#],
+            )
+            .await;
+            subscriptions
+        });
+
+        let stream = KrakenStream::new(url, vec![Pair::new("BTC", "USD"), Pair::new("ETH", "EUR")]);
+        let (tx, mut rx) = mpsc::channel(16);
+        let client = tokio::spawn(async move { stream.run(tx).await });
+
+        let mut prices = Vec::new();
+        for _ in 0..4 {
+            let price = time::timeout(Duration::from_secs(10), rx.recv()).await;
+            prices.push(price.unwrap().unwrap());
+        }
+        assert_eq!(
+            prices,
+            vec![
+                (Pair::new("BTC", "USD"), 100000.0),
+                (Pair::new("BTC", "USD"), 100001.5),
+                (Pair::new("ETH", "EUR"), 3000.0),
+                // Sent over the second connection.
+                (Pair::new("BTC", "USD"), 99000.0),
+            ]
+        );
+
+        let subscriptions = server.await.unwrap();
+        for (subscription, channel) in subscriptions.iter().zip(["ticker", "trade"]) {
+            let subscription: serde_json::Value = serde_json::from_str(subscription).unwrap();
+            assert_eq!(subscription["method"], "subscribe");
+            assert_eq!(subscription["params"]["channel"], channel);
+            assert_eq!(
+                subscription["params"]["symbol"],
+                json!(["BTC/USD", "ETH/EUR"])
+            );
+        }
+
+        drop(rx);
+        client.abort();
+    }
+
+    #[tokio::test]
+    async fn streaming_resets_the_backoff() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            // A failed handshake doubles the backoff to 2s.
+            drop(listener.accept().await.unwrap());
+            // A connection that streams and then drops without a close.
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let trade = r#"{"channel":"trade","data":[{"symbol":"BTC/USD","price":1.0}]}"#;
+            socket.send(Message::text(trade)).await.unwrap();
+            time::sleep(Duration::from_millis(100)).await;
+            drop(socket);
+            let dropped = time::Instant::now();
+            listener.accept().await.unwrap();
+            dropped.elapsed()
+        });
+
+        let stream = KrakenStream::new(url, vec![Pair::new("BTC", "USD")]);
+        let (tx, mut rx) = mpsc::channel(16);
+        let client = tokio::spawn(async move { stream.run(tx).await });
+        let reconnected_after = time::timeout(Duration::from_secs(10), server)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(rx.recv().await.is_some());
+        assert!(
+            reconnected_after < Duration::from_millis(1500),
+            "reconnected after {:?}",
+            reconnected_after
+        );
+        client.abort();
+    }
+
+    #[test]
+    fn ignores_acks_and_unknown_channels() {
+        assert!(parse_prices(r#"{"method":"pong"}"#).is_empty());
+        assert!(parse_prices(r#"{"channel":"book","data":[{}]}"#).is_empty());
+    }
+
+    #[test]
+    fn skips_malformed_entries() {
+        let text = r#"{"channel":"trade","data":[{"symbol":"BTC/USD"},{"symbol":"nonsense","price":1.0},{"symbol":"BTC/USD","price":0.0},{"symbol":"BTC/USD","price":99000.0}]}"#;
+        assert_eq!(parse_prices(text), vec![(Pair::new("BTC", "USD"), 99000.0)]);
+    }
+}
@@ -0,0 +1,55 @@
+use super::{PriceSource, Quote};
+use crate::{metrics, pair::Pair};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Instant;
+
+/// Records fetch counts, errors, latency and prices of the source it wraps.
+pub struct Metered {
+    inner: Box<dyn PriceSource>,
+}
+
+impl Metered {
+    pub fn new(inner: Box<dyn PriceSource>) -> Self {
+        Self { inner }
+    }
+
+    fn record(&self, started: Instant, result: &Result<Vec<Quote>>) {
+        let source = self.inner.name();
+        metrics::FETCHES.with_label_values(&[source]).inc();
+        metrics::FETCH_SECONDS
+            .with_label_values(&[source])
+            .observe(started.elapsed().as_secs_f64());
+        match result {
+            Ok(quotes) => {
+                for quote in quotes {
+                    metrics::PRICE
+                        .with_label_values(&[quote.pair.to_string().as_str(), source])
+                        .set(quote.price);
+                }
+            }
+            Err(e) => metrics::fetch_failed(source, e),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for Metered {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+        let started = Instant::now();
+        let result = self.inner.fetch_quote(pair).await.map(|quote| vec![quote]);
+        self.record(started, &result);
+        result.map(|mut quotes| quotes.remove(0))
+    }
+
+    async fn fetch_quotes(&self, pairs: &[Pair]) -> Result<Vec<Quote>> {
+        let started = Instant::now();
+        let result = self.inner.fetch_quotes(pairs).await;
+        self.record(started, &result);
+        result
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{metrics, pair::Pair};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::warn;
+use reqwest::Client;
+use serde::{Deserialize, Deserializer};
+use std::{fmt, str::FromStr};
+
+mod aggregate;
+mod binance;
+mod bitstamp;
+mod coinbase;
+mod coingecko;
+mod kraken;
+mod kraken_ws;
+mod metered;
+
+pub use aggregate::{AggregateMethod, Aggregator};
+pub use binance::Binance;
+pub use bitstamp::Bitstamp;
+pub use coinbase::Coinbase;
+pub use coingecko::CoinGecko;
+pub use kraken::Kraken;
+pub use kraken_ws::{KrakenStream, WS_URL as KRAKEN_WS_URL};
+pub use metered::Metered;
+
+/// A price quote from a single source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub source: &'static str,
+    pub pair: Pair,
+    pub price: f64,
+    /// Rolling 24h volume in the base asset, when the source reports it.
+    pub volume: Option<f64>,
+}
+
+/// An exchange or aggregator that can quote the current price of a pair.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn fetch_quote(&self, pair: &Pair) -> Result<Quote>;
+
+    /// Quotes several pairs at once. Sources whose API can batch pairs into
+    /// one request should override this; failures for individual pairs are
+    /// logged and left out of the result.
+    async fn fetch_quotes(&self, pairs: &[Pair]) -> Result<Vec<Quote>> {
+        let results = join_all(pairs.iter().map(|pair| self.fetch_quote(pair))).await;
+
+        let mut quotes = Vec::with_capacity(pairs.len());
+        let mut errors = Vec::new();
+        for (pair, result) in pairs.iter().zip(results) {
+            match result {
+                Ok(quote) => quotes.push(quote),
+                Err(e) => {
+                    warn!("Failed to fetch {} price from {}: {}", pair, self.name(), e);
+                    errors.push(e);
+                }
+            }
+        }
+
+        if quotes.is_empty() {
+            if let Some(e) = errors.pop() {
+                return Err(e);
+            }
+        }
+        // The fetch as a whole succeeds, so the pairs that failed are
+        // counted here rather than by the caller.
+        for e in &errors {
+            metrics::fetch_failed(self.name(), e);
+        }
+        Ok(quotes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Kraken,
+    Coinbase,
+    Bitstamp,
+    Binance,
+    CoinGecko,
+}
+
+impl SourceKind {
+    pub const ALL: [SourceKind; 5] = [
+        SourceKind::Kraken,
+        SourceKind::Coinbase,
+        SourceKind::Bitstamp,
+        SourceKind::Binance,
+        SourceKind::CoinGecko,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceKind::Kraken => "kraken",
+            SourceKind::Coinbase => "coinbase",
+            SourceKind::Bitstamp => "bitstamp",
+            SourceKind::Binance => "binance",
+            SourceKind::CoinGecko => "coingecko",
+        }
+    }
+
+    pub fn build(self, client: Client) -> Box<dyn PriceSource> {
+        match self {
+            SourceKind::Kraken => Box::new(Kraken::new(client)),
+            SourceKind::Coinbase => Box::new(Coinbase::new(client)),
+            SourceKind::Bitstamp => Box::new(Bitstamp::new(client)),
+            SourceKind::Binance => Box::new(Binance::new(client)),
+            SourceKind::CoinGecko => Box::new(CoinGecko::new(client)),
+        }
+    }
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SourceKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = SourceKind::ALL.iter().map(|k| k.name()).collect();
+                anyhow::anyhow!(
+                    "Unknown price source {:?}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+pub(crate) fn parse_price(value: &str) -> Result<f64> {
+    check_price(value.parse::<f64>().context("Failed to parse price")?)
+}
+
+/// Rejects prices no market trades at, which would make every move look
+/// infinite.
+pub(crate) fn check_price(price: f64) -> Result<f64> {
+    if !(price.is_finite() && price > 0.0) {
+        anyhow::bail!("Invalid price {}", price);
+    }
+    Ok(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quotes BTC pairs and fails for anything else.
+    struct BtcOnly;
+
+    #[async_trait]
+    impl PriceSource for BtcOnly {
+        fn name(&self) -> &'static str {
+            "btc-only"
+        }
+
+        async fn fetch_quote(&self, pair: &Pair) -> Result<Quote> {
+            anyhow::ensure!(pair.base == "BTC", "No {} price", pair);
+            Ok(Quote {
+                source: self.name(),
+                pair: pair.clone(),
+                price: 100.0,
+                volume: None,
+            })
+        }
+    }
+
+    #[test]
+    fn prices_must_be_positive_and_finite() {
+        assert_eq!(parse_price("101000.5").unwrap(), 101000.5);
+        for price in ["0", "-1", "NaN", "inf", "", "abc"] {
+            assert!(parse_price(price).is_err(), "{}", price);
+        }
+    }
+
+    fn errors() -> f64 {
+        metrics::FETCH_ERRORS
+            .with_label_values(&["btc-only", "response"])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn counts_pairs_dropped_from_a_successful_fetch() {
+        let before = errors();
+        let pairs = [
+            Pair::new("BTC", "USD"),
+            Pair::new("ETH", "USD"),
+            Pair::new("SOL", "USD"),
+        ];
+        let quotes = BtcOnly.fetch_quotes(&pairs).await.unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(errors() - before, 2.0);
+
+        // A fetch that fails outright is left for the caller to count.
+        assert!(BtcOnly.fetch_quotes(&pairs[1..]).await.is_err());
+        assert_eq!(errors() - before, 2.0);
+    }
+}
@@ -0,0 +1,98 @@
+//! A stand-in HTTP server for testing sources and notifiers.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+/// A request received by [`serve`].
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    /// The path and query, e.g. `/0/public/Ticker?pair=XBTUSD`.
+    pub path: String,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Serves HTTP on a local port, answering requests with `responses` in
+/// order and then leaving the rest unanswered. Returns the base URL and the
+/// requests as they arrive.
+pub async fn serve(responses: &[(u16, &str)]) -> (String, mpsc::UnboundedReceiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let responses: VecDeque<_> = responses
+        .iter()
+        .map(|(status, body)| (*status, body.to_string()))
+        .collect();
+    let responses = Arc::new(Mutex::new(responses));
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let responses = responses.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = socket.into_split();
+                let mut read = BufReader::new(read);
+                // Clients keep the connection open for further requests.
+                while let Some(request) = read_request(&mut read).await {
+                    let _ = tx.send(request);
+                    let Some((status, body)) = responses.lock().unwrap().pop_front() else {
+                        std::future::pending::<()>().await;
+                        return;
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    if write.write_all(response.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (url, rx)
+}
+
+async fn read_request<R: AsyncBufReadExt + Unpin>(read: &mut R) -> Option<Request> {
+    let mut line = String::new();
+    read.read_line(&mut line).await.ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        read.read_line(&mut line).await.ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    read.read_exact(&mut body).await.ok()?;
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8(body).ok()?,
+    })
+}